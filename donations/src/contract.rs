@@ -3,12 +3,12 @@
 mod state;
 
 use linera_sdk::{
-    abis::fungible::{Account as FungibleAccount, InitialState, Parameters},
-    linera_base_types::{Account, AccountOwner, WithContractAbi, StreamName, StreamUpdate},
+    abis::fungible::InitialState,
+    linera_base_types::{Account, AccountOwner, Amount, ChainId, WithContractAbi, StreamName, StreamUpdate},
     views::{RootView, View},
     Contract, ContractRuntime,
 };
-use donations::{Message, DonationsAbi, Operation, ResponseData, DonationsEvent, SocialLink};
+use donations::{accounts, split_burn, DonationDetails, CURRENT_STATE_VERSION, MIGRATION_BATCH_SIZE, DataBlobInfo, DonationsCall, DonationsParameters, OrderResponses, Message, DonationsAbi, Operation, ResponseData, DonationsEvent, SocialLink, OutstandingKind, OutstandingMessage};
 use state::DonationsState;
 
 pub struct DonationsContract {
@@ -22,7 +22,7 @@ impl WithContractAbi for DonationsContract { type Abi = DonationsAbi; }
 
 impl Contract for DonationsContract {
     type Message = Message;
-    type Parameters = Parameters;
+    type Parameters = DonationsParameters;
     type InstantiationArgument = InitialState;
     type EventValue = DonationsEvent;
 
@@ -33,7 +33,7 @@ impl Contract for DonationsContract {
 
     async fn instantiate(&mut self, state: Self::InstantiationArgument) {
         for (owner, amount) in state.accounts {
-            let account = Account { chain_id: self.runtime.chain_id(), owner };
+            let account = accounts::on_chain(self.runtime.chain_id(), owner);
             self.runtime.transfer(AccountOwner::CHAIN, account, amount);
         }
     }
@@ -41,35 +41,18 @@ impl Contract for DonationsContract {
     async fn execute_operation(&mut self, operation: Self::Operation) -> Self::Response {
         match operation {
             Operation::Transfer { owner, amount, target_account, text_message } => {
-                self.runtime.check_account_permission(owner).expect("perm");
-                let target_account_norm = self.normalize_account(target_account);
-                self.runtime.transfer(owner, target_account_norm, amount);
-                if target_account_norm.chain_id != self.runtime.chain_id() {
-                    let current_chain = self.runtime.chain_id();
-                    let current_chain_str = current_chain.to_string();
-                    let message = Message::TransferWithMessage { owner: target_account_norm.owner, amount, text_message: text_message.clone(), source_chain_id: current_chain, source_owner: owner };
-                    self.runtime.prepare_message(message).with_authentication().send_to(target_account_norm.chain_id);
-                    let ts = self.runtime.system_time().micros();
-                    if let Ok(id) = self.state.record_donation(owner, target_account_norm.owner, amount, text_message.clone(), Some(current_chain_str.clone()), Some(target_account_norm.chain_id.to_string()), ts).await {
-                        self.runtime.emit("donations_events".into(), &DonationsEvent::DonationSent { id, from: owner, to: target_account_norm.owner, amount, message: text_message, source_chain_id: Some(current_chain_str), to_chain_id: Some(target_account_norm.chain_id.to_string()), timestamp: ts });
-                    }
-                } else {
-                    let ts = self.runtime.system_time().micros();
-                    if let Ok(id) = self.state.record_donation(owner, target_account_norm.owner, amount, text_message.clone(), None, Some(target_account_norm.chain_id.to_string()), ts).await {
-                        self.runtime.emit("donations_events".into(), &DonationsEvent::DonationSent { id, from: owner, to: target_account_norm.owner, amount, message: text_message, source_chain_id: None, to_chain_id: Some(target_account_norm.chain_id.to_string()), timestamp: ts });
-                    }
-                }
+                self.donate(owner, amount, accounts::normalize(target_account), text_message, None).await;
                 ResponseData::Ok
             }
             Operation::Withdraw => {
                 let owner = self.runtime.authenticated_signer().unwrap();
                 let balance = self.runtime.owner_balance(owner);
-                let target_account = Account { chain_id: self.runtime.chain_id(), owner: AccountOwner::CHAIN };
+                let target_account = accounts::on_chain(self.runtime.chain_id(), AccountOwner::CHAIN);
                 self.runtime.transfer(owner, target_account, balance);
                 ResponseData::Ok
             }
             Operation::Mint { owner, amount } => {
-                let target_account = Account { chain_id: self.runtime.chain_id(), owner };
+                let target_account = accounts::on_chain(self.runtime.chain_id(), owner);
                 self.runtime.transfer(AccountOwner::CHAIN, target_account, amount);
                 ResponseData::Ok
             }
@@ -108,10 +91,7 @@ impl Contract for DonationsContract {
                     bio: bio.clone(),
                     socials: socials.iter().map(|s| SocialLink { name: s.name.clone(), url: s.url.clone() }).collect(),
                 };
-                self.runtime
-                    .prepare_message(msg)
-                    .with_authentication()
-                    .send_to(main_chain_id);
+                self.send_tracked(owner, OutstandingMessage::register_id(&owner), OutstandingKind::Register, main_chain_id, msg).await;
                 
                 // Save main_chain_id to subscriptions so we know where to send future messages
                 let _ = self.state.subscriptions.insert(&owner, main_chain_id.to_string());
@@ -196,7 +176,7 @@ impl Contract for DonationsContract {
                     if let Some(main_chain_id_str) = main_chain_str {
                         if let Ok(main_chain_id) = main_chain_id_str.parse() {
                             if main_chain_id != chain_id {
-                                self.runtime.prepare_message(Message::ProductCreated { product }).with_authentication().send_to(main_chain_id);
+                                self.send_tracked(owner, OutstandingMessage::product_id(&product.id), OutstandingKind::ProductCreated, main_chain_id, Message::ProductCreated { product }).await;
                             }
                         }
                     }
@@ -240,6 +220,8 @@ impl Contract for DonationsContract {
             Operation::DeleteProduct { product_id } => {
                 let owner = self.runtime.authenticated_signer().expect("Authentication required");
                 self.state.delete_product(&product_id, owner).await.expect("Failed to delete product");
+                // A ProductCreated still awaiting its ack must not be retried for a deleted product
+                let _ = self.state.acknowledge_outstanding(&OutstandingMessage::product_id(&product_id)).await;
                 
                 let ts = self.runtime.system_time().micros();
                 self.runtime.emit("donations_events".into(), &DonationsEvent::ProductDeleted { product_id: product_id.clone(), author: owner, timestamp: ts });
@@ -259,81 +241,7 @@ impl Contract for DonationsContract {
                 ResponseData::Ok
             }
             Operation::TransferToBuy { owner, product_id, amount, target_account, order_data } => {
-                self.runtime.check_account_permission(owner).expect("Permission denied");
-                
-                // Transfer full amount to author
-                let target_account_norm = self.normalize_account(target_account);
-                self.runtime.transfer(owner, target_account_norm, amount);
-                
-                // Generate purchase ID
-                let ts = self.runtime.system_time().micros();
-                let purchase_id = format!("purchase-{}-{}", ts, self.runtime.chain_id());
-                let buyer_chain_id = self.runtime.chain_id();
-                let seller = target_account_norm.owner;
-                
-                // Emit event
-                self.runtime.emit("donations_events".into(), &DonationsEvent::ProductPurchased {
-                    purchase_id: purchase_id.clone(),
-                    product_id: product_id.clone(),
-                    buyer: owner,
-                    seller,
-                    amount,
-                    timestamp: ts,
-                });
-                
-                // Send purchase message to main chain
-                if let Ok(main_chain_str) = self.state.subscriptions.get(&owner).await {
-                    if let Some(main_chain_id_str) = main_chain_str {
-                        if let Ok(main_chain_id) = main_chain_id_str.parse() {
-                            self.runtime.prepare_message(Message::ProductPurchased {
-                                purchase_id: purchase_id.clone(),
-                                product_id: product_id.clone(),
-                                buyer: owner,
-                                buyer_chain_id,
-                                seller,
-                                amount,
-                            }).with_authentication().send_to(main_chain_id);
-                        }
-                    }
-                }
-                
-                // NEW: Send order notification directly to seller's chain
-                // NEW: Send order notification directly to seller's chain
-                // We trust the target_account chain_id as it comes from the product metadata
-                // and we already transferred funds there.
-                let seller_chain_id = target_account_norm.chain_id;
-
-                if seller_chain_id != buyer_chain_id {
-                    self.runtime.prepare_message(Message::OrderReceived {
-                        purchase_id: purchase_id.clone(),
-                        product_id: product_id.clone(),
-                        buyer: owner,
-                        buyer_chain_id,
-                        amount,
-                        order_data: order_data.clone(),
-                        timestamp: ts,
-                    }).with_authentication().send_to(seller_chain_id);
-                } else {
-                    // Same chain: Record purchase immediately if product exists locally
-                    // This covers local purchases and self-purchases
-                    if let Ok(Some(product)) = self.state.get_product(&product_id).await {
-                         let purchase = donations::Purchase {
-                            id: purchase_id.clone(),
-                            product_id: product_id.clone(),
-                            buyer: owner,
-                            buyer_chain_id: buyer_chain_id.to_string(),
-                            seller,
-                            seller_chain_id: product.author_chain_id.clone(),
-                            // ...
-                            amount,
-                            timestamp: ts,
-                            order_data: order_data.clone(),
-                            product: product.clone(),
-                        };
-                        let _ = self.state.record_purchase(purchase).await;
-                    }
-                }
-                
+                self.buy_product(owner, product_id, amount, accounts::normalize(target_account), order_data, None).await;
                 ResponseData::Ok
             }
             Operation::ReadDataBlob { hash } => {
@@ -344,13 +252,10 @@ impl Contract for DonationsContract {
                     Ok(crypto_hash) => {
                         let blob_hash = DataBlobHash(crypto_hash);
                         let data = self.runtime.read_data_blob(blob_hash);
-                        eprintln!("[READ_BLOB] Read {} bytes from blob {}", data.len(), hash);
-                    }
-                    Err(e) => {
-                        eprintln!("[READ_BLOB] ERROR: Invalid blob hash format '{}': {:?}", hash, e);
+                        ResponseData::Blob(DataBlobInfo { hash, size: data.len() as u64 })
                     }
+                    Err(e) => ResponseData::Error(format!("Invalid blob hash format '{}': {:?}", hash, e)),
                 }
-                ResponseData::Ok
             }
             
             // Content subscription operations
@@ -387,7 +292,8 @@ impl Contract for DonationsContract {
                 let ts = self.runtime.system_time().micros();
                 
                 // Transfer payment to author
-                let target_account_norm = self.normalize_account(target_account);
+                let target_account_norm = accounts::normalize(target_account);
+                accounts::validate_donation_target(&target_account_norm, false).expect("Invalid subscription target");
                 let author = target_account_norm.owner;
                 let author_chain_id = target_account_norm.chain_id;
                 self.runtime.transfer(owner, target_account_norm, amount);
@@ -576,6 +482,55 @@ impl Contract for DonationsContract {
                 
                 ResponseData::Ok
             }
+            
+            Operation::RetryOutstanding { id } => {
+                let signer = self.runtime.authenticated_signer().expect("Authentication required");
+                let mut entry = self.state.get_outstanding(&id).await
+                    .expect("Failed to get outstanding message")
+                    .expect("Outstanding message not found");
+                // Re-sent with the caller's authentication, so it must be the original signer
+                if entry.owner != signer {
+                    panic!("Unauthorized: only the original sender can retry this message");
+                }
+                let target_chain: ChainId = entry.target_chain.parse().expect("Invalid target chain");
+                
+                self.runtime.prepare_message(entry.message.clone()).with_authentication().send_to(target_chain);
+                
+                entry.attempts += 1;
+                entry.sent_at = self.runtime.system_time().micros();
+                self.state.track_outstanding(entry).await.expect("Failed to update outstanding message");
+                
+                ResponseData::Ok
+            }
+            
+            Operation::RunMigration { target_version } => {
+                if target_version != CURRENT_STATE_VERSION {
+                    panic!("Unsupported migration target: {} (current is {})", target_version, CURRENT_STATE_VERSION);
+                }
+                self.state.migrate_step(MIGRATION_BATCH_SIZE).await.expect("Migration failed");
+                ResponseData::Ok
+            }
+            
+            Operation::ApplicationCall { call } => {
+                // Only allowlisted applications may move funds through this app; they pay from their own account
+                let caller = self.runtime.authenticated_caller_id().expect("Application call requires a calling application");
+                let caller_str = caller.to_string();
+                if !self.runtime.application_parameters().allowed_callers.contains(&caller_str) {
+                    panic!("Unauthorized: calling application is not allowlisted");
+                }
+                let source = AccountOwner::from(caller);
+                
+                match call {
+                    DonationsCall::Donate { to, amount, message } => {
+                        self.donate(source, amount, accounts::normalize(to), message, Some(caller_str)).await;
+                    }
+                    DonationsCall::Purchase { product_id, amount, author } => {
+                        self.buy_product(source, product_id, amount, accounts::normalize(author), OrderResponses::new(), Some(caller_str)).await;
+                    }
+                }
+                
+                ResponseData::Ok
+            }
         }
     }
 
@@ -583,25 +538,40 @@ impl Contract for DonationsContract {
         match message {
             Message::Notify => {}
             Message::TransferWithMessage { owner, amount, text_message, source_chain_id, source_owner } => {
-                let ts = self.runtime.system_time().micros();
-                let current_chain_id = self.runtime.chain_id().to_string();
-                if let Ok(id) = self.state.record_donation(source_owner, owner, amount, text_message.clone(), Some(source_chain_id.to_string()), Some(current_chain_id.clone()), ts).await {
-                    self.runtime.emit("donations_events".into(), &DonationsEvent::DonationSent { id, from: source_owner, to: owner, amount, message: text_message, source_chain_id: Some(source_chain_id.to_string()), to_chain_id: Some(current_chain_id), timestamp: ts });
-                }
+                self.receive_donation(owner, amount, text_message, source_chain_id, source_owner, DonationDetails::default()).await;
+            }
+            Message::TransferWithDetails { owner, amount, text_message, source_chain_id, source_owner, details } => {
+                self.receive_donation(owner, amount, text_message, source_chain_id, source_owner, details).await;
             }
             Message::Register { source_chain_id, owner, name, bio, socials } => {
-                // Subscribe this (main) chain to the source chain's donations_events stream
-                let app_id = self.runtime.application_id().forget_abi();
-                let stream = StreamName::from("donations_events");
-                self.runtime.subscribe_to_events(source_chain_id, app_id, stream.clone());
-                let _ = self.state.subscriptions.insert(&owner, source_chain_id.to_string());
-                if let Some(n) = name { let _ = self.state.set_name(owner, n).await; }
-                if let Some(b) = bio { let _ = self.state.set_bio(owner, b).await; }
-                for s in socials { let _ = self.state.set_social(owner, s.name, s.url).await; }
+                // Subscribe this (main) chain to the source chain's donations_events stream.
+                // A retried Register is only acknowledged again: later profile edits arrive through the
+                // stream, and the stale copy carried by the retry must not overwrite them.
+                let already_registered = self.state.subscriptions.get(&owner).await.ok().flatten() == Some(source_chain_id.to_string());
+                if !already_registered {
+                    let app_id = self.runtime.application_id().forget_abi();
+                    let stream = StreamName::from("donations_events");
+                    self.runtime.subscribe_to_events(source_chain_id, app_id, stream.clone());
+                    let _ = self.state.subscriptions.insert(&owner, source_chain_id.to_string());
+                    let ts = self.runtime.system_time().micros();
+                    let _ = self.state.record_chain_activity(source_chain_id.to_string(), false, ts).await;
+                    if let Some(n) = name { let _ = self.state.set_name(owner, n).await; }
+                    if let Some(b) = bio { let _ = self.state.set_bio(owner, b).await; }
+                    for s in socials { let _ = self.state.set_social(owner, s.name, s.url).await; }
+                }
+                self.runtime.prepare_message(Message::RegisterAck { owner }).send_to(source_chain_id);
             }
             Message::ProductCreated { product } => {
-                // Main chain stores product from other chains
-                let _ = self.state.create_product(product).await;
+                // Main chain stores product from other chains and acknowledges it (again, if retried).
+                // Chain activity counts the matching stream event, not this message.
+                let product_id = product.id.clone();
+                let author_chain_id = product.author_chain_id.clone();
+                if !matches!(self.state.get_product(&product_id).await, Ok(Some(_))) {
+                    let _ = self.state.create_product(product).await;
+                }
+                if let Ok(author_chain_id) = author_chain_id.parse::<ChainId>() {
+                    self.runtime.prepare_message(Message::ProductAck { product_id }).send_to(author_chain_id);
+                }
             }
             Message::ProductUpdated { product } => {
                 // Main chain updates product
@@ -615,7 +585,23 @@ impl Contract for DonationsContract {
                 let _ = self.state.delete_product(&product_id, author).await;
             }
             Message::ProductPurchased { purchase_id, product_id, buyer, buyer_chain_id, seller, amount } => {
-                // Main chain receives purchase notification and sends product data to buyer
+                // Main chain receives purchase notification and sends product data to buyer.
+                // A retried notification is only answered again so the buyer's chain gets its ack, and only
+                // for sales this handler validated: a purchase mirrored from the stream never passed the price check.
+                if let Ok(Some(existing)) = self.state.purchases.get(&purchase_id).await {
+                    if let Ok(Some(sent_to)) = self.state.product_data_sent_to(&purchase_id).await {
+                        if let Ok(sent_to) = sent_to.parse::<ChainId>() {
+                            self.runtime.prepare_message(Message::SendProductData {
+                                buyer: existing.buyer,
+                                purchase_id,
+                                product: existing.product,
+                            }).with_authentication().send_to(sent_to);
+                        }
+                    }
+                    return;
+                }
+                // Chain activity counts the buyer chain's stream event, not this message
+                let ts = self.runtime.system_time().micros();
                 if let Ok(Some(product)) = self.state.get_product(&product_id).await {
                     // Validate that the paid amount matches the product price
                     if amount == product.price {
@@ -627,7 +613,6 @@ impl Contract for DonationsContract {
                         }).with_authentication().send_to(buyer_chain_id);
                         
                         // Record purchase on main chain
-                        let ts = self.runtime.system_time().micros();
                         let purchase = donations::Purchase {
                             id: purchase_id.clone(),
                             product_id: product_id.clone(),
@@ -639,8 +624,10 @@ impl Contract for DonationsContract {
                             timestamp: ts,
                             order_data: std::collections::BTreeMap::new(), // Main chain doesn't have order data
                             product,
+                            via_application: None, // Nor the calling application (see Purchase::via_application)
                         };
                         let _ = self.state.record_purchase(purchase).await;
+                        let _ = self.state.mark_product_data_sent(&purchase_id, buyer_chain_id.to_string());
                         
                         // Emit event so subscribers to Main Chain see the purchase
                         self.runtime.emit("donations_events".into(), &DonationsEvent::ProductPurchased {
@@ -655,7 +642,8 @@ impl Contract for DonationsContract {
                 }
             }
             Message::SendProductData { buyer, purchase_id, product } => {
-                // Buyer's chain receives full product data
+                // Buyer's chain receives full product data (this also acknowledges ProductPurchased)
+                self.acknowledge_from_target(OutstandingMessage::purchase_id(&purchase_id)).await;
                 let ts = self.runtime.system_time().micros();
                 let purchase = donations::Purchase {
                     id: purchase_id,
@@ -668,40 +656,15 @@ impl Contract for DonationsContract {
                     timestamp: ts,
                     order_data: std::collections::BTreeMap::new(), // Empty for now
                     product,
+                    via_application: None, // Kept in purchase_applications since buy_product
                 };
                 let _ = self.state.record_purchase(purchase).await;
             }
             Message::OrderReceived { purchase_id, product_id, buyer, buyer_chain_id, amount, order_data, timestamp } => {
-                // Seller's chain receives order notification with buyer's form data
-                // We must fetch the product to get the correct seller (author) and to record the purchase
-                if let Ok(Some(product)) = self.state.get_product(&product_id).await {
-                    let seller = product.author; // Correct seller is the product author
-
-                    // Record the full purchase so it shows up in "My Orders"
-                    let purchase = donations::Purchase {
-                        id: purchase_id.clone(),
-                        product_id: product_id.clone(),
-                        buyer,
-                        buyer_chain_id: buyer_chain_id.to_string(),
-                        seller,
-                        seller_chain_id: product.author_chain_id.clone(),
-                        amount,
-                        timestamp,
-                        order_data: order_data.clone(),
-                        product: product.clone(),
-                    };
-                    
-                    let _ = self.state.record_purchase(purchase).await;
-
-                    self.runtime.emit("donations_events".into(), &DonationsEvent::OrderPlaced {
-                        purchase_id,
-                        product_id,
-                        buyer,
-                        seller,
-                        amount,
-                        timestamp,
-                    });
-                }
+                self.receive_order(purchase_id, product_id, buyer, buyer_chain_id, amount, order_data, None, timestamp).await;
+            }
+            Message::OrderReceivedViaApplication { purchase_id, product_id, buyer, buyer_chain_id, amount, order_data, timestamp, via_application } => {
+                self.receive_order(purchase_id, product_id, buyer, buyer_chain_id, amount, order_data, Some(via_application), timestamp).await;
             }
             Message::SubscriptionPayment { subscriber, subscriber_chain_id, author, amount, duration_micros, timestamp } => {
                 // Author's chain receives subscription payment
@@ -745,6 +708,12 @@ impl Contract for DonationsContract {
                 // Subscriber's chain deletes the post
                 let _ = self.state.delete_post(&post_id, author).await;
             }
+            Message::RegisterAck { owner } => {
+                self.acknowledge_from_target(OutstandingMessage::register_id(&owner)).await;
+            }
+            Message::ProductAck { product_id } => {
+                self.acknowledge_from_target(OutstandingMessage::product_id(&product_id)).await;
+            }
         }
     }
 
@@ -752,7 +721,212 @@ impl Contract for DonationsContract {
 }
 
 impl DonationsContract {
-    fn normalize_account(&self, account: FungibleAccount) -> Account { Account { chain_id: account.chain_id, owner: account.owner } }
+    /// Donation transfer shared by Operation::Transfer and application calls
+    async fn donate(&mut self, owner: AccountOwner, amount: Amount, target_account_norm: Account, text_message: Option<String>, via_application: Option<String>) {
+        self.runtime.check_account_permission(owner).expect("perm");
+        accounts::validate_donation_target(&target_account_norm, false).expect("Invalid donation target");
+        // Burn share (if enabled) goes to an account nobody can spend from and is recorded on the donation
+        let (amount, burned_amount) = split_burn(amount, self.runtime.application_parameters().burn_bps);
+        self.runtime.transfer(owner, target_account_norm, amount);
+        if burned_amount > Amount::ZERO {
+            self.runtime.transfer(owner, accounts::on_chain(self.runtime.chain_id(), accounts::BURN_SINK), burned_amount);
+            self.state.add_burned(burned_amount);
+        }
+        let details = DonationDetails { burned_amount, via_application };
+        if target_account_norm.chain_id != self.runtime.chain_id() {
+            let current_chain = self.runtime.chain_id();
+            let current_chain_str = current_chain.to_string();
+            let message = Message::TransferWithMessage { owner: target_account_norm.owner, amount, text_message: text_message.clone(), source_chain_id: current_chain, source_owner: owner }
+                .with_details(details.clone());
+            self.runtime.prepare_message(message).with_authentication().send_to(target_account_norm.chain_id);
+            let ts = self.runtime.system_time().micros();
+            if let Ok(id) = self.state.record_donation(owner, target_account_norm.owner, amount, text_message.clone(), Some(current_chain_str.clone()), Some(target_account_norm.chain_id.to_string()), details.clone(), ts).await {
+                let event = DonationsEvent::DonationSent { id, from: owner, to: target_account_norm.owner, amount, message: text_message, source_chain_id: Some(current_chain_str), to_chain_id: Some(target_account_norm.chain_id.to_string()), timestamp: ts };
+                self.runtime.emit("donations_events".into(), &event.with_details(details));
+            }
+        } else {
+            let ts = self.runtime.system_time().micros();
+            if let Ok(id) = self.state.record_donation(owner, target_account_norm.owner, amount, text_message.clone(), None, Some(target_account_norm.chain_id.to_string()), details.clone(), ts).await {
+                let event = DonationsEvent::DonationSent { id, from: owner, to: target_account_norm.owner, amount, message: text_message, source_chain_id: None, to_chain_id: Some(target_account_norm.chain_id.to_string()), timestamp: ts };
+                self.runtime.emit("donations_events".into(), &event.with_details(details));
+            }
+        }
+    }
+
+    /// Record a donation received from another chain and re-emit it on this chain's stream
+    async fn receive_donation(&mut self, owner: AccountOwner, amount: Amount, text_message: Option<String>, source_chain_id: ChainId, source_owner: AccountOwner, details: DonationDetails) {
+        let ts = self.runtime.system_time().micros();
+        let current_chain_id = self.runtime.chain_id().to_string();
+        let _ = self.state.record_chain_activity(current_chain_id.clone(), true, ts).await;
+        if let Ok(id) = self.state.record_donation(source_owner, owner, amount, text_message.clone(), Some(source_chain_id.to_string()), Some(current_chain_id.clone()), details.clone(), ts).await {
+            let event = DonationsEvent::DonationSent { id, from: source_owner, to: owner, amount, message: text_message, source_chain_id: Some(source_chain_id.to_string()), to_chain_id: Some(current_chain_id), timestamp: ts };
+            self.runtime.emit("donations_events".into(), &event.with_details(details));
+        }
+    }
+
+    /// Seller's side of an order placed on another chain
+    async fn receive_order(&mut self, purchase_id: String, product_id: String, buyer: AccountOwner, buyer_chain_id: ChainId, amount: Amount, order_data: OrderResponses, via_application: Option<String>, timestamp: u64) {
+        // Seller's chain receives order notification with buyer's form data
+        // We must fetch the product to get the correct seller (author) and to record the purchase
+        if let Ok(Some(product)) = self.state.get_product(&product_id).await {
+            let seller = product.author; // Correct seller is the product author
+
+            // Record the full purchase so it shows up in "My Orders"
+            let purchase = donations::Purchase {
+                id: purchase_id.clone(),
+                product_id: product_id.clone(),
+                buyer,
+                buyer_chain_id: buyer_chain_id.to_string(),
+                seller,
+                seller_chain_id: product.author_chain_id.clone(),
+                amount,
+                timestamp,
+                order_data: order_data.clone(),
+                product: product.clone(),
+                via_application,
+            };
+            
+            let _ = self.state.record_purchase(purchase).await;
+
+            self.runtime.emit("donations_events".into(), &DonationsEvent::OrderPlaced {
+                purchase_id,
+                product_id,
+                buyer,
+                seller,
+                amount,
+                timestamp,
+            });
+        }
+    }
+
+    /// Product purchase shared by Operation::TransferToBuy and application calls
+    async fn buy_product(&mut self, owner: AccountOwner, product_id: String, amount: Amount, target_account_norm: Account, order_data: OrderResponses, via_application: Option<String>) {
+        self.runtime.check_account_permission(owner).expect("Permission denied");
+        
+        // Transfer full amount to author
+        let known_product = self.state.get_product(&product_id).await.ok().flatten();
+        accounts::validate_purchase_target(&target_account_norm, known_product.as_ref()).expect("Invalid purchase target");
+        self.runtime.transfer(owner, target_account_norm, amount);
+        
+        // Generate purchase ID
+        let ts = self.runtime.system_time().micros();
+        let purchase_id = format!("purchase-{}-{}", ts, self.runtime.chain_id());
+        let buyer_chain_id = self.runtime.chain_id();
+        let seller = target_account_norm.owner;
+        // The buyer's copy of the purchase arrives later through SendProductData, which doesn't carry the caller
+        if let Some(app) = via_application.clone() {
+            self.state.set_purchase_application(&purchase_id, app).expect("Failed to record calling application");
+        }
+        
+        // Emit event
+        self.runtime.emit("donations_events".into(), &DonationsEvent::ProductPurchased {
+            purchase_id: purchase_id.clone(),
+            product_id: product_id.clone(),
+            buyer: owner,
+            seller,
+            amount,
+            timestamp: ts,
+        });
+        
+        // Send purchase message to main chain
+        let mut notified_chain = None;
+        if let Ok(main_chain_str) = self.state.subscriptions.get(&owner).await {
+            if let Some(main_chain_id_str) = main_chain_str {
+                if let Ok(main_chain_id) = main_chain_id_str.parse() {
+                    notified_chain = Some(main_chain_id);
+                    self.send_tracked(owner, OutstandingMessage::purchase_id(&purchase_id), OutstandingKind::ProductPurchased, main_chain_id, Message::ProductPurchased {
+                        purchase_id: purchase_id.clone(),
+                        product_id: product_id.clone(),
+                        buyer: owner,
+                        buyer_chain_id,
+                        seller,
+                        amount,
+                    }).await;
+                }
+            }
+        }
+        
+        // NEW: Send order notification directly to seller's chain
+        // We trust the target_account chain_id as it comes from the product metadata
+        // and we already transferred funds there.
+        let seller_chain_id = target_account_norm.chain_id;
+
+        if seller_chain_id != buyer_chain_id {
+            self.runtime.prepare_message(Message::OrderReceived {
+                purchase_id: purchase_id.clone(),
+                product_id: product_id.clone(),
+                buyer: owner,
+                buyer_chain_id,
+                amount,
+                order_data: order_data.clone(),
+                timestamp: ts,
+            }.with_application(via_application.clone())).with_authentication().send_to(seller_chain_id);
+
+            // A chain holding a copy of the product (the main chain) records the sale itself, unless the
+            // ProductPurchased above went to it: it skips its own stream, and application callers send none
+            if let Some(product) = known_product.filter(|product| product.price == amount && notified_chain != Some(buyer_chain_id)) {
+                let purchase = donations::Purchase {
+                    id: purchase_id.clone(),
+                    product_id: product_id.clone(),
+                    buyer: owner,
+                    buyer_chain_id: buyer_chain_id.to_string(),
+                    seller,
+                    seller_chain_id: product.author_chain_id.clone(),
+                    amount,
+                    timestamp: ts,
+                    order_data: order_data.clone(),
+                    product,
+                    via_application: via_application.clone(),
+                };
+                let _ = self.state.record_purchase(purchase).await;
+            }
+        } else {
+            // Same chain: Record purchase immediately if product exists locally
+            // This covers local purchases and self-purchases
+            if let Ok(Some(product)) = self.state.get_product(&product_id).await {
+                 let purchase = donations::Purchase {
+                    id: purchase_id.clone(),
+                    product_id: product_id.clone(),
+                    buyer: owner,
+                    buyer_chain_id: buyer_chain_id.to_string(),
+                    seller,
+                    seller_chain_id: product.author_chain_id.clone(),
+                    // ...
+                    amount,
+                    timestamp: ts,
+                    order_data: order_data.clone(),
+                    product: product.clone(),
+                    via_application: via_application.clone(),
+                };
+                let _ = self.state.record_purchase(purchase).await;
+            }
+        }
+    }
+
+    /// Send a message that the target chain acknowledges, tracking it until the ack arrives
+    async fn send_tracked(&mut self, owner: AccountOwner, id: String, kind: OutstandingKind, target_chain: ChainId, message: Message) {
+        self.runtime.prepare_message(message.clone()).with_authentication().send_to(target_chain);
+        let entry = OutstandingMessage {
+            id,
+            kind,
+            owner,
+            target_chain: target_chain.to_string(),
+            sent_at: self.runtime.system_time().micros(),
+            attempts: 1,
+            message,
+        };
+        let _ = self.state.track_outstanding(entry).await;
+    }
+
+    /// Clear an outstanding entry, but only when the ack comes from the chain it was sent to
+    async fn acknowledge_from_target(&mut self, id: String) {
+        if let Ok(Some(entry)) = self.state.get_outstanding(&id).await {
+            if self.runtime.message_origin_chain_id().map(|chain_id| chain_id.to_string()) == Some(entry.target_chain) {
+                let _ = self.state.acknowledge_outstanding(&id).await;
+            }
+        }
+    }
+
     async fn process_streams(&mut self, streams: Vec<StreamUpdate>) {
         let current_chain = self.runtime.chain_id();
         for stream_update in streams {
@@ -760,6 +934,9 @@ impl DonationsContract {
             for index in stream_update.previous_index..stream_update.next_index {
                 let stream_name = stream_update.stream_id.stream_name.clone();
                 let event = self.runtime.read_event(stream_update.chain_id, stream_name, index);
+                let ts = self.runtime.system_time().micros();
+                let _ = self.state.record_stream_event(stream_update.chain_id.to_string(), &event, ts).await;
+                self.state.add_burned(event.burned_on(&stream_update.chain_id.to_string()));
                 match event {
                     DonationsEvent::ProfileNameUpdated { owner, name, timestamp: _ } => {
                         let _ = self.state.set_name(owner, name).await;
@@ -776,8 +953,8 @@ impl DonationsContract {
                     DonationsEvent::ProfileHeaderUpdated { owner, hash, timestamp: _ } => {
                         let _ = self.state.set_header(owner, hash).await;
                     }
-                    DonationsEvent::DonationSent { id: _, from, to, amount, message, source_chain_id, to_chain_id, timestamp } => {
-                        let _ = self.state.record_donation(from, to, amount, message, source_chain_id, to_chain_id, timestamp).await;
+                    donation @ (DonationsEvent::DonationSent { .. } | DonationsEvent::DonationSentWithDetails { .. }) => {
+                        let _ = self.state.mirror_donation(&stream_update.chain_id.to_string(), donation).await;
                     }
                     DonationsEvent::ProductCreated { product, timestamp: _ } => {
                         let _ = self.state.create_product(product).await;
@@ -801,6 +978,7 @@ impl DonationsContract {
                                 timestamp,
                                 order_data: std::collections::BTreeMap::new(), // Event doesn't contain order_data
                                 product,
+                                via_application: None, // Nor the calling application (see Purchase::via_application)
                             };
                             let _ = self.state.record_purchase(purchase).await;
                         }
@@ -839,3 +1017,651 @@ impl DonationsContract {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use donations::{
+        accounts, DonationDetails, DonationsCall, DonationsEvent, DonationsParameters, Message, Operation, OutstandingKind, OutstandingMessage, Product, Purchase, RevenueBucket,
+        MAX_REVENUE_WEEKS, OUTSTANDING_TIMEOUT_MICROS, WEEK_MICROS,
+    };
+    use linera_sdk::{
+        abis::fungible,
+        linera_base_types::{AccountOwner, Amount, ApplicationId, ChainId, Timestamp},
+        util::BlockingWait,
+        views::View,
+        Contract, ContractRuntime,
+    };
+
+    use super::{DonationsContract, DonationsState};
+
+    fn owner(n: u8) -> AccountOwner {
+        format!("0x{}", format!("{:02x}", n).repeat(32)).parse().expect("valid owner")
+    }
+
+    fn chain(n: u8) -> ChainId {
+        format!("{:02x}", n).repeat(32).parse().expect("valid chain id")
+    }
+
+    fn empty_state() -> DonationsState {
+        let runtime = ContractRuntime::<DonationsContract>::new();
+        DonationsState::load(runtime.root_view_storage_context())
+            .blocking_wait()
+            .expect("Failed to read from mock key value store")
+    }
+
+    fn contract_on(chain_id: ChainId, signer: AccountOwner, now: u64) -> DonationsContract {
+        contract_with(DonationsParameters::default(), chain_id, signer, now, None)
+    }
+
+    // `caller` is another application (a game, say) calling into this one; it pays from its own account
+    fn contract_with(parameters: DonationsParameters, chain_id: ChainId, signer: AccountOwner, now: u64, caller: Option<ApplicationId>) -> DonationsContract {
+        let mut runtime = ContractRuntime::new()
+            .with_application_parameters(parameters)
+            .with_chain_id(chain_id)
+            .with_authenticated_signer(signer)
+            .with_system_time(Timestamp::from(now));
+        if let Some(app) = caller {
+            runtime = runtime
+                .with_authenticated_caller_id(app)
+                .with_owner_balance(AccountOwner::from(app), Amount::from_tokens(10));
+        }
+        let state = DonationsState::load(runtime.root_view_storage_context())
+            .blocking_wait()
+            .expect("Failed to read from mock key value store");
+        DonationsContract { state, runtime }
+    }
+
+    fn register(main_chain_id: ChainId) -> Operation {
+        Operation::Register {
+            main_chain_id,
+            name: Some("alice".to_string()),
+            bio: None,
+            socials: Vec::new(),
+            avatar_hash: None,
+            header_hash: None,
+        }
+    }
+
+    fn product(id: &str, author: AccountOwner, author_chain: ChainId, price: Amount) -> Product {
+        Product {
+            id: id.to_string(),
+            author,
+            author_chain_id: author_chain.to_string(),
+            public_data: BTreeMap::new(),
+            price,
+            private_data: BTreeMap::new(),
+            success_message: None,
+            order_form: Vec::new(),
+            created_at: 0,
+        }
+    }
+
+    fn purchase(id: &str, product: &Product, buyer: AccountOwner, timestamp: u64) -> Purchase {
+        Purchase {
+            id: id.to_string(),
+            product_id: product.id.clone(),
+            buyer,
+            buyer_chain_id: chain(99).to_string(),
+            seller: product.author,
+            seller_chain_id: product.author_chain_id.clone(),
+            amount: product.price,
+            timestamp,
+            order_data: BTreeMap::new(),
+            product: product.clone(),
+            via_application: None,
+        }
+    }
+
+    fn donation_event(from: AccountOwner, to: AccountOwner, source_chain: Option<ChainId>, to_chain: ChainId) -> DonationsEvent {
+        DonationsEvent::DonationSent {
+            id: 1,
+            from,
+            to,
+            amount: Amount::ONE,
+            message: None,
+            source_chain_id: source_chain.map(|c| c.to_string()),
+            to_chain_id: Some(to_chain.to_string()),
+            timestamp: 0,
+        }
+    }
+
+    fn buckets(series: &[RevenueBucket]) -> Vec<(u64, Amount)> {
+        series.iter().map(|b| (b.week, b.amount)).collect()
+    }
+
+    #[test]
+    fn donation_message_is_sanitized_on_read() {
+        let mut state = empty_state();
+        let message = Some("<b>gg</b>  I <3 this".to_string());
+        let id = state
+            .record_donation(owner(1), owner(2), Amount::ONE, message.clone(), None, None, DonationDetails::default(), 0)
+            .blocking_wait()
+            .unwrap();
+
+        let rec = state.get_donation(id).blocking_wait().unwrap().unwrap();
+        assert_eq!(rec.message, message);
+        assert_eq!(rec.message_sanitized.as_deref(), Some("gg I &lt;3 this"));
+        let listed = state.list_donations_by_recipient(owner(2)).blocking_wait().unwrap();
+        assert_eq!(listed[0].message_sanitized, rec.message_sanitized);
+    }
+
+    #[test]
+    fn record_purchase_indexes_each_sale_once() {
+        let mut state = empty_state();
+        let (seller, buyer) = (owner(1), owner(2));
+        let item = product("p1", seller, chain(1), Amount::ONE);
+
+        // The main chain sees a sale from the ProductPurchased message and again from the buyer's stream
+        state.record_purchase(purchase("sale-1", &item, buyer, 10)).blocking_wait().unwrap();
+        state.record_purchase(purchase("sale-1", &item, buyer, 10)).blocking_wait().unwrap();
+
+        assert_eq!(state.list_purchases_by_seller(seller).blocking_wait().unwrap().len(), 1);
+        assert_eq!(state.list_purchases_by_buyer(buyer).blocking_wait().unwrap().len(), 1);
+        assert_eq!(state.list_purchases_by_product("p1").blocking_wait().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn revenue_rolls_over_at_week_boundary() {
+        let mut state = empty_state();
+        let seller = owner(1);
+        let item = product("p1", seller, chain(1), Amount::from_tokens(2));
+
+        state.record_purchase(purchase("sale-1", &item, owner(2), WEEK_MICROS - 1)).blocking_wait().unwrap();
+        state.record_purchase(purchase("sale-2", &item, owner(3), WEEK_MICROS)).blocking_wait().unwrap();
+
+        let series = state.revenue_series(seller, 0, 1).blocking_wait().unwrap();
+        assert_eq!(buckets(&series), vec![(0, Amount::from_tokens(2)), (1, Amount::from_tokens(2))]);
+    }
+
+    #[test]
+    fn refund_in_later_week_decrements_the_sale_week() {
+        let mut state = empty_state();
+        let seller = owner(1);
+        state.add_revenue(seller, WEEK_MICROS - 1, Amount::from_tokens(5)).blocking_wait().unwrap();
+        state.add_revenue(seller, WEEK_MICROS + 10, Amount::from_tokens(1)).blocking_wait().unwrap();
+
+        // The refund is issued in week 1 but belongs to the week-0 sale
+        state.subtract_revenue(seller, WEEK_MICROS - 1, Amount::from_tokens(5)).blocking_wait().unwrap();
+
+        let series = state.revenue_series(seller, 0, 1).blocking_wait().unwrap();
+        assert_eq!(buckets(&series), vec![(0, Amount::ZERO), (1, Amount::from_tokens(1))]);
+    }
+
+    #[test]
+    fn revenue_series_zero_fills_gaps() {
+        let mut state = empty_state();
+        let seller = owner(1);
+        state.add_revenue(seller, WEEK_MICROS, Amount::from_tokens(1)).blocking_wait().unwrap();
+        state.add_revenue(seller, 3 * WEEK_MICROS, Amount::from_tokens(4)).blocking_wait().unwrap();
+        // Other sellers never leak into the series
+        state.add_revenue(owner(2), 2 * WEEK_MICROS, Amount::from_tokens(7)).blocking_wait().unwrap();
+
+        let series = state.revenue_series(seller, 0, 4).blocking_wait().unwrap();
+        assert_eq!(
+            buckets(&series),
+            vec![
+                (0, Amount::ZERO),
+                (1, Amount::from_tokens(1)),
+                (2, Amount::ZERO),
+                (3, Amount::from_tokens(4)),
+                (4, Amount::ZERO),
+            ]
+        );
+    }
+
+    #[test]
+    fn revenue_series_is_clamped() {
+        let state = empty_state();
+        let seller = owner(1);
+
+        let series = state.revenue_series(seller, 10, 10_000).blocking_wait().unwrap();
+        assert_eq!(series.len() as u64, MAX_REVENUE_WEEKS);
+        assert_eq!(series.last().map(|b| b.week), Some(10 + MAX_REVENUE_WEEKS - 1));
+
+        assert!(state.revenue_series(seller, 5, 4).blocking_wait().unwrap().is_empty());
+    }
+
+    #[test]
+    fn unacknowledged_registration_is_listed_and_retried() {
+        let (creator, main_chain) = (owner(1), chain(9));
+        let mut contract = contract_on(chain(1), creator, 1_000);
+        contract.execute_operation(register(main_chain)).blocking_wait();
+
+        // The main chain never answers: the registration stays outstanding
+        let id = OutstandingMessage::register_id(&creator);
+        let entry = contract.state.get_outstanding(&id).blocking_wait().unwrap().expect("registration is tracked");
+        assert_eq!(entry.kind, OutstandingKind::Register);
+        assert_eq!(entry.owner, creator);
+        assert_eq!(entry.target_chain, main_chain.to_string());
+        assert_eq!(entry.attempts, 1);
+        assert!(!entry.is_overdue(1_000, OUTSTANDING_TIMEOUT_MICROS));
+        let later = 1_000 + OUTSTANDING_TIMEOUT_MICROS;
+        assert!(entry.is_overdue(later, OUTSTANDING_TIMEOUT_MICROS));
+
+        contract.runtime.set_system_time(Timestamp::from(later));
+        contract.execute_operation(Operation::RetryOutstanding { id: id.clone() }).blocking_wait();
+
+        let entry = contract.state.get_outstanding(&id).blocking_wait().unwrap().expect("still tracked until acked");
+        assert_eq!(entry.attempts, 2);
+        assert_eq!(entry.sent_at, later);
+        let registrations = contract.runtime.created_send_message_requests().iter()
+            .filter(|request| request.destination == main_chain && matches!(request.message, Message::Register { .. }))
+            .count();
+        assert_eq!(registrations, 2);
+
+        // An ack from any other chain is ignored
+        contract.runtime.set_message_origin_chain_id(chain(5));
+        contract.execute_message(Message::RegisterAck { owner: creator }).blocking_wait();
+        assert!(contract.state.get_outstanding(&id).blocking_wait().unwrap().is_some());
+
+        contract.runtime.set_message_origin_chain_id(main_chain);
+        contract.execute_message(Message::RegisterAck { owner: creator }).blocking_wait();
+        assert!(contract.state.get_outstanding(&id).blocking_wait().unwrap().is_none());
+    }
+
+    #[test]
+    fn retried_registration_keeps_the_current_profile() {
+        let (creator, source_chain) = (owner(1), chain(1));
+        let mut contract = contract_on(chain(9), owner(9), 0);
+        let registration = Message::Register { source_chain_id: source_chain, owner: creator, name: Some("alice".to_string()), bio: None, socials: Vec::new() };
+        contract.execute_message(registration.clone()).blocking_wait();
+
+        // The rename reaches the main chain through the stream before the retry does
+        contract.state.set_name(creator, "alice2".to_string()).blocking_wait().unwrap();
+        contract.execute_message(registration).blocking_wait();
+
+        let profile = contract.state.get_profile(creator).blocking_wait().unwrap().expect("profile stored");
+        assert_eq!(profile.name, "alice2");
+        let acks = contract.runtime.created_send_message_requests().iter()
+            .filter(|request| request.destination == source_chain && matches!(request.message, Message::RegisterAck { .. }))
+            .count();
+        assert_eq!(acks, 2);
+    }
+
+    #[test]
+    fn deleted_product_is_not_left_outstanding() {
+        let creator = owner(1);
+        let mut contract = contract_on(chain(1), creator, 1_000);
+        contract.execute_operation(register(chain(9))).blocking_wait();
+        contract
+            .execute_operation(Operation::CreateProduct {
+                public_data: BTreeMap::new(),
+                price: Amount::ONE,
+                private_data: BTreeMap::new(),
+                success_message: None,
+                order_form: Vec::new(),
+            })
+            .blocking_wait();
+        let product_id = format!("1000-{}", chain(1));
+        let id = OutstandingMessage::product_id(&product_id);
+        assert!(contract.state.get_outstanding(&id).blocking_wait().unwrap().is_some());
+
+        contract.execute_operation(Operation::DeleteProduct { product_id }).blocking_wait();
+        assert!(contract.state.get_outstanding(&id).blocking_wait().unwrap().is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "Unauthorized")]
+    fn only_the_original_signer_can_retry() {
+        let creator = owner(1);
+        let mut contract = contract_on(chain(1), creator, 1_000);
+        contract.execute_operation(register(chain(9))).blocking_wait();
+
+        contract.runtime.set_authenticated_signer(owner(2));
+        contract.execute_operation(Operation::RetryOutstanding { id: OutstandingMessage::register_id(&creator) }).blocking_wait();
+    }
+
+    #[test]
+    fn retried_purchase_notification_is_recorded_once() {
+        let (main_chain, buyer_chain, seller, buyer) = (chain(9), chain(2), owner(1), owner(2));
+        let mut contract = contract_on(main_chain, owner(9), 5_000);
+        let item = product("p1", seller, chain(1), Amount::ONE);
+        contract.state.create_product(item).blocking_wait().unwrap();
+
+        let notification = Message::ProductPurchased {
+            purchase_id: "purchase-1".to_string(),
+            product_id: "p1".to_string(),
+            buyer,
+            buyer_chain_id: buyer_chain,
+            seller,
+            amount: Amount::ONE,
+        };
+        contract.execute_message(notification.clone()).blocking_wait();
+        contract.execute_message(notification).blocking_wait();
+
+        assert_eq!(contract.state.list_purchases_by_seller(seller).blocking_wait().unwrap().len(), 1);
+        assert_eq!(contract.state.list_purchases_by_buyer(buyer).blocking_wait().unwrap().len(), 1);
+        let series = contract.state.revenue_series(seller, 0, 0).blocking_wait().unwrap();
+        assert_eq!(buckets(&series), vec![(0, Amount::ONE)]);
+        // Counted from the buyer chain's stream only
+        assert!(contract.state.get_chain_activity(&buyer_chain.to_string()).blocking_wait().unwrap().is_none());
+
+        // Both deliveries are answered so the buyer's chain can clear its outstanding entry
+        let acks = contract.runtime.created_send_message_requests().iter()
+            .filter(|request| request.destination == buyer_chain && matches!(request.message, Message::SendProductData { .. }))
+            .count();
+        assert_eq!(acks, 2);
+    }
+
+    #[test]
+    fn underpaid_purchase_from_the_stream_is_not_answered_on_retry() {
+        let (main_chain, buyer_chain, seller, buyer) = (chain(9), chain(2), owner(1), owner(2));
+        let mut contract = contract_on(main_chain, owner(9), 5_000);
+        let item = product("p1", seller, chain(1), Amount::ONE);
+        contract.state.create_product(item.clone()).blocking_wait().unwrap();
+
+        // Mirrored from the buyer chain's stream, which carries no price check
+        let mut underpaid = purchase("purchase-1", &item, buyer, 0);
+        underpaid.amount = Amount::from_millis(1);
+        contract.state.record_purchase(underpaid).blocking_wait().unwrap();
+
+        contract
+            .execute_message(Message::ProductPurchased {
+                purchase_id: "purchase-1".to_string(),
+                product_id: "p1".to_string(),
+                buyer,
+                buyer_chain_id: buyer_chain,
+                seller,
+                amount: Amount::from_millis(1),
+            })
+            .blocking_wait();
+
+        assert!(!contract.runtime.created_send_message_requests().iter().any(|request| matches!(request.message, Message::SendProductData { .. })));
+    }
+
+    #[test]
+    fn created_product_is_counted_once() {
+        let author_chain = chain(1);
+        let mut contract = contract_on(chain(9), owner(9), 5_000);
+        let item = product("p1", owner(1), author_chain, Amount::ONE);
+        contract.execute_message(Message::ProductCreated { product: item.clone() }).blocking_wait();
+        // The author chain's stream carries the same creation
+        let event = DonationsEvent::ProductCreated { product: item, timestamp: 0 };
+        contract.state.record_stream_event(author_chain.to_string(), &event, 5_000).blocking_wait().unwrap();
+
+        let activity = contract.state.get_chain_activity(&author_chain.to_string()).blocking_wait().unwrap().expect("author chain seen");
+        assert_eq!(activity.event_count, 1);
+    }
+
+    #[test]
+    fn chain_activity_counts_each_received_donation_once() {
+        let mut state = empty_state();
+        let (alice, bob) = (owner(1), owner(2));
+        let (alice_chain, bob_chain) = (chain(1), chain(2));
+        // Registration, as done by the Register message handler on the main chain
+        for (creator, creator_chain) in [(alice, alice_chain), (bob, bob_chain)] {
+            state.subscriptions.insert(&creator, creator_chain.to_string()).unwrap();
+            state.record_chain_activity(creator_chain.to_string(), false, 10).blocking_wait().unwrap();
+        }
+
+        // Alice donates to Bob: the donation is mirrored from both chains' streams
+        let cross_chain = donation_event(alice, bob, Some(alice_chain), bob_chain);
+        state.record_stream_event(alice_chain.to_string(), &cross_chain, 20).blocking_wait().unwrap();
+        state.record_stream_event(bob_chain.to_string(), &cross_chain, 30).blocking_wait().unwrap();
+        // A donation to Alice made on her own chain only appears on her stream
+        let local = donation_event(owner(3), alice, None, alice_chain);
+        state.record_stream_event(alice_chain.to_string(), &local, 40).blocking_wait().unwrap();
+        let renamed = DonationsEvent::ProfileNameUpdated { owner: bob, name: "bob".to_string(), timestamp: 50 };
+        state.record_stream_event(bob_chain.to_string(), &renamed, 50).blocking_wait().unwrap();
+
+        let alice_activity = state.get_chain_activity(&alice_chain.to_string()).blocking_wait().unwrap().expect("alice registered");
+        assert_eq!((alice_activity.donation_count, alice_activity.event_count, alice_activity.last_event_at), (1, 3, Some(40)));
+        let bob_activity = state.get_chain_activity(&bob_chain.to_string()).blocking_wait().unwrap().expect("bob registered");
+        assert_eq!((bob_activity.donation_count, bob_activity.event_count, bob_activity.last_event_at), (1, 3, Some(50)));
+    }
+
+    #[test]
+    fn mirrored_donation_is_recorded_once() {
+        let mut state = empty_state();
+        let (alice, bob) = (owner(1), owner(2));
+        let (alice_chain, bob_chain) = (chain(1), chain(2));
+
+        // Alice donates to Bob: both chains emit the donation, only Bob's copy is kept
+        let cross_chain = donation_event(alice, bob, Some(alice_chain), bob_chain);
+        state.mirror_donation(&alice_chain.to_string(), cross_chain.clone()).blocking_wait().unwrap();
+        state.mirror_donation(&bob_chain.to_string(), cross_chain).blocking_wait().unwrap();
+        // A donation made on Bob's own chain only appears on his stream
+        let local = donation_event(owner(3), bob, None, bob_chain);
+        state.mirror_donation(&bob_chain.to_string(), local).blocking_wait().unwrap();
+
+        let received = state.list_donations_by_recipient(bob).blocking_wait().unwrap();
+        assert_eq!(received.len(), 2);
+        assert_eq!(received[0].source_chain_id, Some(alice_chain.to_string()));
+        assert_eq!(received[1].source_chain_id, None);
+        assert_eq!(state.list_donations_by_donor(alice).blocking_wait().unwrap().len(), 1);
+    }
+
+    fn burning_donor(chain_id: ChainId, burn_bps: u32) -> DonationsContract {
+        let parameters = DonationsParameters { burn_bps, ..DonationsParameters::default() };
+        let mut contract = contract_with(parameters, chain_id, owner(1), 0, None);
+        contract.runtime.set_owner_balance(owner(1), Amount::from_tokens(10));
+        contract
+    }
+
+    fn donate(to_chain: ChainId) -> Operation {
+        Operation::Transfer {
+            owner: owner(1),
+            amount: Amount::from_tokens(10),
+            target_account: fungible::Account { chain_id: to_chain, owner: owner(2) },
+            text_message: None,
+        }
+    }
+
+    #[test]
+    fn burned_share_goes_to_the_sink_and_is_counted_once() {
+        let mut contract = burning_donor(chain(1), 100);
+        contract.execute_operation(donate(chain(1))).blocking_wait();
+
+        let burned = Amount::from_millis(100);
+        assert_eq!(*contract.state.total_burned.get(), burned);
+        assert_eq!(contract.runtime.owner_balance(accounts::BURN_SINK), burned);
+        assert_eq!(contract.runtime.owner_balance(AccountOwner::CHAIN), Amount::ZERO);
+        assert_eq!(contract.runtime.owner_balance(owner(2)), Amount::from_millis(9_900));
+        let rec = contract.state.get_donation(1).blocking_wait().unwrap().unwrap();
+        assert_eq!(rec.amount, Amount::from_millis(9_900));
+        assert_eq!(rec.burned_amount, burned);
+    }
+
+    #[test]
+    fn cross_chain_donation_only_uses_the_details_message_when_burning() {
+        let mut contract = burning_donor(chain(1), 0);
+        contract.execute_operation(donate(chain(2))).blocking_wait();
+        let sent = contract.runtime.created_send_message_requests();
+        assert!(matches!(sent.last().map(|r| &r.message), Some(Message::TransferWithMessage { .. })));
+        assert_eq!(*contract.state.total_burned.get(), Amount::ZERO);
+        assert!(contract.state.donation_details.get(&1).blocking_wait().unwrap().is_none());
+
+        let mut contract = burning_donor(chain(1), 100);
+        contract.execute_operation(donate(chain(2))).blocking_wait();
+        let sent = contract.runtime.created_send_message_requests();
+        match sent.last().map(|r| r.message.clone()) {
+            Some(Message::TransferWithDetails { details, .. }) => assert_eq!(details.burned_amount, Amount::from_millis(100)),
+            other => panic!("unexpected message {:?}", other),
+        }
+    }
+
+    #[test]
+    fn recipient_records_the_burn_without_counting_it() {
+        let mut recipient = contract_on(chain(2), owner(2), 0);
+        let details = DonationDetails { burned_amount: Amount::from_millis(100), via_application: None };
+        recipient
+            .execute_message(Message::TransferWithDetails {
+                owner: owner(2),
+                amount: Amount::from_millis(9_900),
+                text_message: None,
+                source_chain_id: chain(1),
+                source_owner: owner(1),
+                details,
+            })
+            .blocking_wait();
+
+        let rec = recipient.state.get_donation(1).blocking_wait().unwrap().unwrap();
+        assert_eq!(rec.burned_amount, Amount::from_millis(100));
+        assert_eq!(*recipient.state.total_burned.get(), Amount::ZERO);
+    }
+
+    fn caller_app(n: u8) -> ApplicationId {
+        format!("{:02x}", n).repeat(32).parse().expect("valid application id")
+    }
+
+    fn called_by(app: ApplicationId, allowed_callers: Vec<String>, chain_id: ChainId) -> DonationsContract {
+        let parameters = DonationsParameters { allowed_callers, ..DonationsParameters::default() };
+        contract_with(parameters, chain_id, owner(9), 0, Some(app))
+    }
+
+    fn donate_call(to_chain: ChainId) -> Operation {
+        Operation::ApplicationCall {
+            call: DonationsCall::Donate {
+                to: fungible::Account { chain_id: to_chain, owner: owner(2) },
+                amount: Amount::from_tokens(4),
+                message: Some("gg".to_string()),
+            },
+        }
+    }
+
+    #[test]
+    fn example_caller_donates_from_its_own_account() {
+        let game = caller_app(7);
+        let mut contract = called_by(game, vec![game.to_string()], chain(1));
+        contract.execute_operation(donate_call(chain(1))).blocking_wait();
+
+        assert_eq!(contract.runtime.owner_balance(owner(2)), Amount::from_tokens(4));
+        assert_eq!(contract.runtime.owner_balance(AccountOwner::from(game)), Amount::from_tokens(6));
+        let rec = contract.state.get_donation(1).blocking_wait().unwrap().unwrap();
+        assert_eq!(rec.from, AccountOwner::from(game));
+        assert_eq!(rec.via_application, Some(game.to_string()));
+    }
+
+    #[test]
+    fn example_caller_is_carried_to_the_recipient_chain() {
+        let game = caller_app(7);
+        let mut contract = called_by(game, vec![game.to_string()], chain(1));
+        contract.execute_operation(donate_call(chain(2))).blocking_wait();
+
+        let sent = contract.runtime.created_send_message_requests();
+        match sent.last().map(|r| r.message.clone()) {
+            Some(Message::TransferWithDetails { details, .. }) => assert_eq!(details.via_application, Some(game.to_string())),
+            other => panic!("unexpected message {:?}", other),
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "not allowlisted")]
+    fn caller_not_on_the_allowlist_is_rejected() {
+        let game = caller_app(7);
+        let mut contract = called_by(game, vec![caller_app(8).to_string()], chain(1));
+        contract.execute_operation(donate_call(chain(1))).blocking_wait();
+    }
+
+    fn purchase_call(author: AccountOwner, author_chain: ChainId) -> Operation {
+        Operation::ApplicationCall {
+            call: DonationsCall::Purchase {
+                product_id: "p1".to_string(),
+                amount: Amount::ONE,
+                author: fungible::Account { chain_id: author_chain, owner: author },
+            },
+        }
+    }
+
+    #[test]
+    fn example_caller_purchase_is_attributed_on_buyer_and_seller_chains() {
+        // The buyer's chain doesn't know the product; the call names the author's account
+        let game = caller_app(7);
+        let item = product("p1", owner(2), chain(2), Amount::ONE);
+        let mut buyer = called_by(game, vec![game.to_string()], chain(1));
+        buyer.execute_operation(purchase_call(owner(2), chain(2))).blocking_wait();
+        assert!(buyer.state.list_purchases_by_buyer(AccountOwner::from(game)).blocking_wait().unwrap().is_empty());
+
+        let order = buyer.runtime.created_send_message_requests().iter()
+            .find(|request| request.destination == chain(2))
+            .map(|request| request.message.clone())
+            .expect("order sent to the seller");
+        assert!(matches!(&order, Message::OrderReceivedViaApplication { via_application, .. } if *via_application == game.to_string()));
+
+        let mut seller = contract_on(chain(2), owner(2), 0);
+        seller.state.create_product(item.clone()).blocking_wait().unwrap();
+        seller.execute_message(order).blocking_wait();
+        let sold = seller.state.list_purchases_by_seller(owner(2)).blocking_wait().unwrap();
+        assert_eq!(sold.len(), 1);
+        assert_eq!(sold[0].via_application, Some(game.to_string()));
+
+        // The buyer's copy comes back through SendProductData, which doesn't carry the caller
+        let purchase_id = sold[0].id.clone();
+        buyer
+            .execute_message(Message::SendProductData { buyer: AccountOwner::from(game), purchase_id: purchase_id.clone(), product: item })
+            .blocking_wait();
+        let bought = buyer.state.get_purchase(&purchase_id).blocking_wait().unwrap().unwrap();
+        assert_eq!(bought.via_application, Some(game.to_string()));
+    }
+
+    #[test]
+    fn example_caller_purchase_is_recorded_on_the_main_chain() {
+        let game = caller_app(7);
+        let mut main = called_by(game, vec![game.to_string()], chain(9));
+        main.state.create_product(product("p1", owner(2), chain(2), Amount::ONE)).blocking_wait().unwrap();
+        main.execute_operation(purchase_call(owner(2), chain(2))).blocking_wait();
+
+        let sold = main.state.list_purchases_by_seller(owner(2)).blocking_wait().unwrap();
+        assert_eq!(sold.len(), 1);
+        assert_eq!(sold[0].via_application, Some(game.to_string()));
+        assert_eq!(buckets(&main.state.revenue_series(owner(2), 0, 0).blocking_wait().unwrap()), vec![(0, Amount::ONE)]);
+        let by_product = main.state.purchases_by_product.get(&"p1".to_string()).blocking_wait().unwrap().unwrap_or_default();
+        assert_eq!(by_product, vec![sold[0].id.clone()]);
+    }
+
+    #[test]
+    #[should_panic(expected = "not the product author")]
+    fn example_caller_purchase_must_pay_the_known_author() {
+        let game = caller_app(7);
+        let mut main = called_by(game, vec![game.to_string()], chain(9));
+        main.state.create_product(product("p1", owner(2), chain(2), Amount::ONE)).blocking_wait().unwrap();
+        main.execute_operation(purchase_call(owner(3), chain(2))).blocking_wait();
+    }
+
+    #[test]
+    fn chain_without_purchases_starts_at_the_current_version() {
+        let mut state = empty_state();
+        assert!(!state.migration_required().blocking_wait().unwrap());
+        let item = product("p1", owner(2), chain(2), Amount::ONE);
+        state.record_purchase(purchase("purchase-a", &item, owner(1), 0)).blocking_wait().unwrap();
+        assert!(state.is_migrated());
+        assert!(!state.migration_required().blocking_wait().unwrap());
+    }
+
+    #[test]
+    fn v0_purchases_are_migrated_in_two_chunks() {
+        let mut state = empty_state();
+        let item = product("p1", owner(2), chain(2), Amount::ONE);
+        // Purchases written by v0 bytecode, before the revenue and per-product indexes existed
+        for (id, timestamp) in [("purchase-a", 0), ("purchase-b", WEEK_MICROS), ("purchase-c", WEEK_MICROS + 1)] {
+            state.purchases.insert(&id.to_string(), purchase(id, &item, owner(1), timestamp)).unwrap();
+        }
+
+        assert!(state.migration_required().blocking_wait().unwrap());
+
+        // A sale made by the upgraded bytecode before the migration runs is indexed right away
+        state.record_purchase(purchase("purchase-d", &item, owner(3), WEEK_MICROS)).blocking_wait().unwrap();
+        assert!(!state.is_migrated());
+
+        // Four purchases in batches of two: the cursor stops after the first chunk
+        assert!(!state.migrate_step(2).blocking_wait().unwrap());
+        assert!(!state.is_migrated());
+        assert!(state.migration_cursor.get().is_some());
+        assert!(state.migrate_step(2).blocking_wait().unwrap());
+        assert!(state.migration_cursor.get().is_none());
+        assert!(state.is_migrated());
+        assert!(!state.migration_required().blocking_wait().unwrap());
+
+        let expected_revenue = vec![(0, Amount::ONE), (1, Amount::from_tokens(3))];
+        assert_eq!(buckets(&state.revenue_series(owner(2), 0, 1).blocking_wait().unwrap()), expected_revenue);
+        let mut indexed = state.purchases_by_product.get(&"p1".to_string()).blocking_wait().unwrap().unwrap_or_default();
+        indexed.sort();
+        assert_eq!(indexed, vec!["purchase-a", "purchase-b", "purchase-c", "purchase-d"]);
+
+        // Running it again changes nothing
+        assert!(state.migrate_step(2).blocking_wait().unwrap());
+        assert_eq!(buckets(&state.revenue_series(owner(2), 0, 1).blocking_wait().unwrap()), expected_revenue);
+    }
+}
@@ -1,4 +1,4 @@
-use async_graphql::{Request, Response, SimpleObject, InputObject};
+use async_graphql::{Request, Response, SimpleObject, InputObject, Enum};
 use linera_sdk::linera_base_types::{AccountOwner, Amount, ContractAbi, ServiceAbi, ChainId};
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
@@ -7,7 +7,7 @@ use std::collections::BTreeMap;
 pub type CustomFields = BTreeMap<String, String>;
 pub type OrderResponses = BTreeMap<String, String>;
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub enum Message {
     Notify,
     TransferWithMessage {
@@ -76,6 +76,71 @@ pub enum Message {
         post_id: String,
         author: AccountOwner,
     },
+    // Acknowledgements for tracked outbound messages
+    RegisterAck {
+        owner: AccountOwner,
+    },
+    ProductAck {
+        product_id: String,
+    },
+    // TransferWithMessage for a donation that carries details (see DonationDetails)
+    TransferWithDetails {
+        owner: AccountOwner,
+        amount: Amount,
+        text_message: Option<String>,
+        source_chain_id: ChainId,
+        source_owner: AccountOwner,
+        details: DonationDetails,
+    },
+    // OrderReceived for a purchase made through Operation::ApplicationCall
+    OrderReceivedViaApplication {
+        purchase_id: String,
+        product_id: String,
+        buyer: AccountOwner,
+        buyer_chain_id: ChainId,
+        amount: Amount,
+        order_data: OrderResponses,
+        timestamp: u64,
+        via_application: String,
+    },
+}
+
+impl Message {
+    /// Attach donation details; a donation without any stays a plain TransferWithMessage
+    pub fn with_details(self, details: DonationDetails) -> Self {
+        match self {
+            Message::TransferWithMessage { owner, amount, text_message, source_chain_id, source_owner } if !details.is_empty() => {
+                Message::TransferWithDetails { owner, amount, text_message, source_chain_id, source_owner, details }
+            }
+            other => other,
+        }
+    }
+
+    /// Attach the calling application to an order; a direct purchase stays a plain OrderReceived
+    pub fn with_application(self, via_application: Option<String>) -> Self {
+        match (self, via_application) {
+            (Message::OrderReceived { purchase_id, product_id, buyer, buyer_chain_id, amount, order_data, timestamp }, Some(via_application)) => {
+                Message::OrderReceivedViaApplication { purchase_id, product_id, buyer, buyer_chain_id, amount, order_data, timestamp, via_application }
+            }
+            (other, _) => other,
+        }
+    }
+}
+
+// Optional donation data that is not part of the original message, event and record layouts.
+// It travels in the *WithDetails variants and is stored next to the donation record, so a
+// donation without details is encoded exactly as before.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DonationDetails {
+    pub burned_amount: Amount,
+    // Calling application when the donation came in through a cross-application call
+    pub via_application: Option<String>,
+}
+
+impl DonationDetails {
+    pub fn is_empty(&self) -> bool {
+        *self == DonationDetails::default()
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize, InputObject)]
@@ -137,9 +202,18 @@ pub struct DonationRecord {
     pub from: AccountOwner,
     pub to: AccountOwner,
     pub amount: Amount,
+    // Filled from the donation's details when the record is read, so it is not part of the stored layout
+    #[serde(skip)]
+    pub burned_amount: Amount,
     pub message: Option<String>,
+    // Derived from `message` when the record is read, so it is not part of the stored layout
+    #[serde(skip)]
+    pub message_sanitized: Option<String>,
     pub source_chain_id: Option<String>,
     pub to_chain_id: Option<String>,
+    // Filled from the donation's details when the record is read, so it is not part of the stored layout
+    #[serde(skip)]
+    pub via_application: Option<String>,
 }
 
 // Content subscription structure
@@ -176,7 +250,50 @@ pub struct DonationView {
     pub to_owner: AccountOwner,
     pub to_chain_id: String,
     pub amount: Amount,
+    // Sanitized message (safe to render in HTML overlays)
+    pub message: Option<String>,
+    pub raw_message: Option<String>,
+}
+
+// Per-chain activity counters (maintained on the main chain). `donation_count` counts
+// donations received by creators on the chain; `event_count` counts everything seen from it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, SimpleObject)]
+pub struct ChainActivity {
+    pub chain_id: String,
+    pub donation_count: u64,
+    pub event_count: u64,
+    pub last_event_at: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
+pub struct RegisteredChainView {
+    pub chain_id: String,
+    pub owner: AccountOwner,
+    pub profile_name: Option<String>,
+    pub donation_count: u64,
+    pub last_event_at: Option<u64>,
+}
+
+// Activity feed entry kinds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Enum)]
+pub enum ActivityKind {
+    DonationReceived,
+    ProductPublished,
+    ProductSold,
+}
+
+// Activity feed entry - payload fields are filled depending on kind
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
+pub struct ActivityItem {
+    pub id: String,
+    pub kind: ActivityKind,
+    pub timestamp: u64,
+    pub amount: Option<Amount>,
+    pub counterparty: Option<AccountOwner>,
+    pub counterparty_chain_id: Option<String>,
     pub message: Option<String>,
+    pub product_id: Option<String>,
+    pub purchase_id: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
@@ -258,6 +375,12 @@ pub struct Purchase {
     
     // Product snapshot at time of purchase
     pub product: Product,
+    
+    // Calling application when the purchase came in through a cross-application call. Only the
+    // buyer's and seller's chains know it (main-chain copies leave it empty); it is stored next to
+    // the purchase and filled in when read, so it is not part of the stored layout.
+    #[serde(skip)]
+    pub via_application: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
@@ -273,6 +396,71 @@ pub struct PurchaseView {
     pub product: ProductView,
 }
 
+// Seller revenue for one week bucket
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
+pub struct RevenueBucket {
+    pub week: u64,
+    pub amount: Amount,
+}
+
+pub const WEEK_MICROS: u64 = 7 * 24 * 60 * 60 * 1_000_000;
+// Longest revenue series returned by a single query (about ten years)
+pub const MAX_REVENUE_WEEKS: u64 = 520;
+
+/// Week bucket (weeks since the Unix epoch) for a timestamp in microseconds
+pub fn week_bucket(timestamp: u64) -> u64 {
+    timestamp / WEEK_MICROS
+}
+
+// Outbound cross-chain messages that expect an acknowledgement
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Enum)]
+pub enum OutstandingKind {
+    Register,
+    ProductCreated,
+    ProductPurchased,
+}
+
+// Tracked outbound message, removed when the target chain acknowledges it
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
+pub struct OutstandingMessage {
+    pub id: String,
+    pub kind: OutstandingKind,
+    // Signer of the original operation; only they may retry it
+    pub owner: AccountOwner,
+    pub target_chain: String,
+    pub sent_at: u64,
+    pub attempts: u32,
+    #[graphql(skip)]
+    pub message: Message,
+}
+
+impl OutstandingMessage {
+    pub fn register_id(owner: &AccountOwner) -> String { format!("register:{}", owner) }
+    pub fn product_id(product_id: &str) -> String { format!("product:{}", product_id) }
+    pub fn purchase_id(purchase_id: &str) -> String { format!("purchase:{}", purchase_id) }
+
+    /// True once the message has gone unacknowledged for `timeout_micros`
+    pub fn is_overdue(&self, now: u64, timeout_micros: u64) -> bool {
+        self.sent_at.saturating_add(timeout_micros) <= now
+    }
+}
+
+// State layout version expected by this bytecode. Version 1 adds the revenue and
+// purchases-by-product indexes, which must be backfilled on chains with older purchases.
+pub const CURRENT_STATE_VERSION: u32 = 1;
+// Purchases processed per RunMigration operation
+pub const MIGRATION_BATCH_SIZE: usize = 100;
+
+// Messages unacknowledged for this long are reported by the outstandingMessages query
+pub const OUTSTANDING_TIMEOUT_MICROS: u64 = 5 * 60 * 1_000_000;
+
+// Data blob metadata (hash and size in bytes)
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
+pub struct DataBlobInfo {
+    pub hash: String,
+    pub size: u64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum DonationsEvent {
     ProfileNameUpdated { owner: AccountOwner, name: String, timestamp: u64 },
@@ -295,6 +483,282 @@ pub enum DonationsEvent {
     PostCreated { post: Post, timestamp: u64 },
     PostUpdated { post: Post, timestamp: u64 },
     PostDeleted { post_id: String, author: AccountOwner, timestamp: u64 },
+    // DonationSent for a donation that carries details (see DonationDetails)
+    DonationSentWithDetails { id: u64, from: AccountOwner, to: AccountOwner, amount: Amount, message: Option<String>, source_chain_id: Option<String>, to_chain_id: Option<String>, timestamp: u64, details: DonationDetails },
+}
+
+impl DonationsEvent {
+    /// True for a donation whose recipient is on `chain_id`. A cross-chain donation is emitted
+    /// on both the sender's and the recipient's stream; only the recipient's copy matches.
+    pub fn is_donation_received_on(&self, chain_id: &str) -> bool {
+        matches!(
+            self,
+            DonationsEvent::DonationSent { to_chain_id: Some(to), .. } | DonationsEvent::DonationSentWithDetails { to_chain_id: Some(to), .. }
+                if to == chain_id
+        )
+    }
+
+    /// Share burned on `chain_id` by this event. The burn happens on the sender's chain, so the
+    /// recipient's re-emitted copy of a cross-chain donation reports nothing.
+    pub fn burned_on(&self, chain_id: &str) -> Amount {
+        match self {
+            DonationsEvent::DonationSentWithDetails { source_chain_id, details, .. }
+                if source_chain_id.as_deref().is_none_or(|source| source == chain_id) => details.burned_amount,
+            _ => Amount::ZERO,
+        }
+    }
+
+    /// Attach donation details; a donation without any stays a plain DonationSent
+    pub fn with_details(self, details: DonationDetails) -> Self {
+        match self {
+            DonationsEvent::DonationSent { id, from, to, amount, message, source_chain_id, to_chain_id, timestamp } if !details.is_empty() => {
+                DonationsEvent::DonationSentWithDetails { id, from, to, amount, message, source_chain_id, to_chain_id, timestamp, details }
+            }
+            other => other,
+        }
+    }
+}
+
+// Account helpers shared by every transfer path in the contract
+pub mod accounts {
+    use super::Product;
+    use linera_sdk::abis::fungible;
+    use linera_sdk::linera_base_types::{Account, AccountOwner, ChainId};
+
+    /// Convert a fungible ABI account into a base account
+    pub fn normalize(account: fungible::Account) -> Account {
+        Account { chain_id: account.chain_id, owner: account.owner }
+    }
+
+    /// Account of `owner` on `chain_id` (used for local mint/withdraw/instantiate transfers)
+    pub fn on_chain(chain_id: ChainId, owner: AccountOwner) -> Account {
+        Account { chain_id, owner }
+    }
+
+    /// Destination of burned donation shares. No signer or application can authenticate as a
+    /// reserved owner other than CHAIN, so unlike the chain balance (which Mint draws from)
+    /// tokens sent here can never be moved again.
+    pub const BURN_SINK: AccountOwner = AccountOwner::Reserved(1);
+
+    /// Donations and subscription payments must go to a user, not a chain balance, unless explicitly allowed
+    pub fn validate_donation_target(account: &Account, allow_chain_owner: bool) -> Result<(), String> {
+        if account.owner == AccountOwner::CHAIN && !allow_chain_owner {
+            return Err("Invalid target: chain account cannot receive donations".to_string());
+        }
+        Ok(())
+    }
+
+    /// Purchases must pay the product author on the author's chain (checked when the product is known locally)
+    pub fn validate_purchase_target(account: &Account, product: Option<&Product>) -> Result<(), String> {
+        if account.owner == AccountOwner::CHAIN {
+            return Err("Invalid target: chain account cannot sell products".to_string());
+        }
+        if let Some(product) = product {
+            if account.owner != product.author {
+                return Err("Invalid target: not the product author".to_string());
+            }
+            if account.chain_id.to_string() != product.author_chain_id {
+                return Err("Invalid target: not the product author's chain".to_string());
+            }
+        }
+        Ok(())
+    }
+}
+
+// Donation message sanitization for HTML overlays
+pub const MAX_EMOJI_RUN: usize = 5;
+
+fn is_emoji_modifier(c: char) -> bool {
+    // Variation selector, skin tones, keycap and tag characters attach to the preceding emoji
+    matches!(c as u32, 0xFE0F | 0x1F3FB..=0x1F3FF | 0x20E3 | 0xE0020..=0xE007F)
+}
+
+fn is_emoji(c: char) -> bool {
+    // Pictographs, symbols and dingbats
+    matches!(c as u32, 0x1F000..=0x1FAFF | 0x2600..=0x27BF | 0x2B00..=0x2BFF) && !is_emoji_modifier(c)
+}
+
+fn is_regional_indicator(c: char) -> bool {
+    matches!(c as u32, 0x1F1E6..=0x1F1FF)
+}
+
+/// Byte length of the emoji cluster `s` starts with, or 0. A cluster is an emoji with its
+/// modifiers and any ZWJ-joined emoji that follow it, or a pair of regional indicators (a flag).
+fn emoji_cluster_len(s: &str) -> usize {
+    let mut chars = s.char_indices();
+    let first = match chars.next() {
+        Some((_, c)) if is_emoji(c) => c,
+        _ => return 0,
+    };
+    let mut end = first.len_utf8();
+    if is_regional_indicator(first) {
+        return match chars.next() {
+            Some((i, c)) if is_regional_indicator(c) => i + c.len_utf8(),
+            _ => end,
+        };
+    }
+    loop {
+        let mut lookahead = chars.clone();
+        match lookahead.next() {
+            Some((i, c)) if is_emoji_modifier(c) => end = i + c.len_utf8(),
+            Some((_, '\u{200D}')) => match lookahead.next() {
+                Some((i, c)) if is_emoji(c) => end = i + c.len_utf8(),
+                _ => return end,
+            },
+            _ => return end,
+        }
+        chars = lookahead;
+    }
+}
+
+/// Sanitized projection of a donation message: strips HTML tags, collapses whitespace,
+/// limits emoji runs to MAX_EMOJI_RUN emoji and escapes any remaining angle brackets
+pub fn sanitize_message(raw: &str) -> String {
+    // Drop complete tags ('<' followed by a letter or '/'); any other '<' is kept and escaped below
+    let mut stripped = String::with_capacity(raw.len());
+    let mut rest = raw;
+    while let Some(start) = rest.find('<') {
+        stripped.push_str(&rest[..start]);
+        let candidate = &rest[start..];
+        let is_tag = candidate[1..].starts_with(|c: char| c.is_ascii_alphabetic() || c == '/');
+        match candidate.find('>') {
+            Some(end) if is_tag => rest = &candidate[end + 1..],
+            _ => {
+                stripped.push('<');
+                rest = &candidate[1..];
+            }
+        }
+    }
+    stripped.push_str(rest);
+
+    let collapsed = stripped.split_whitespace().collect::<Vec<_>>().join(" ");
+    let mut out = String::with_capacity(collapsed.len());
+    let mut emoji_run = 0;
+    let mut rest = collapsed.as_str();
+    while let Some(c) = rest.chars().next() {
+        let cluster = emoji_cluster_len(rest);
+        if cluster > 0 {
+            emoji_run += 1;
+            if emoji_run <= MAX_EMOJI_RUN {
+                out.push_str(&rest[..cluster]);
+            }
+            rest = &rest[cluster..];
+            continue;
+        }
+        emoji_run = 0;
+        match c {
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            _ => out.push(c),
+        }
+        rest = &rest[c.len_utf8()..];
+    }
+    out
+}
+
+// Application parameters (shared by every chain). Fields are optional in the JSON so
+// existing deployments that only pass `ticker_symbol` keep working.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DonationsParameters {
+    #[serde(default)]
+    pub ticker_symbol: String,
+    // Share of every donation that is burned, in basis points (0 disables burning)
+    #[serde(default)]
+    pub burn_bps: u32,
+    // Application ids allowed to donate and purchase through Operation::ApplicationCall
+    #[serde(default)]
+    pub allowed_callers: Vec<String>,
+}
+
+/// Split a donation into (recipient share, burned share). The burn is rounded down so
+/// rounding always favors the recipient; `burn_bps` above 10000 is treated as 10000.
+pub fn split_burn(amount: Amount, burn_bps: u32) -> (Amount, Amount) {
+    if burn_bps == 0 {
+        return (amount, Amount::ZERO);
+    }
+    let bps = u128::from(burn_bps.min(10_000));
+    let attos = u128::from(amount);
+    // (attos / 10000) * bps + (attos % 10000) * bps / 10000 computes floor(attos * bps / 10000) without overflow
+    let burned = Amount::from_attos((attos / 10_000) * bps + (attos % 10_000) * bps / 10_000);
+    (amount.saturating_sub(burned), burned)
+}
+
+// Amount parsing and formatting used at the GraphQL boundary
+pub mod amounts {
+    use async_graphql::Enum;
+    use linera_sdk::linera_base_types::Amount;
+    use serde::{Deserialize, Serialize};
+    use std::fmt;
+
+    pub const DECIMAL_PLACES: usize = 18;
+    const ATTOS_PER_TOKEN: u128 = 1_000_000_000_000_000_000;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Enum)]
+    pub enum AmountError {
+        Empty,
+        InvalidFormat,
+        TooManyDecimals,
+        Overflow,
+    }
+
+    impl fmt::Display for AmountError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                AmountError::Empty => write!(f, "amount is empty"),
+                AmountError::InvalidFormat => write!(f, "amount must be a decimal token value (e.g. \"1.5\") or an integer with an \"atto\" suffix"),
+                AmountError::TooManyDecimals => write!(f, "amount has more than {} decimal places", DECIMAL_PLACES),
+                AmountError::Overflow => write!(f, "amount is too large"),
+            }
+        }
+    }
+
+    fn is_digits(s: &str) -> bool {
+        s.bytes().all(|b| b.is_ascii_digit())
+    }
+
+    /// Parse a user-supplied amount: either a decimal token value ("1", "0.25", ".5")
+    /// or an integer number of attos with an explicit suffix ("250000000000000000atto(s)")
+    pub fn parse_user_amount(input: &str) -> Result<Amount, AmountError> {
+        let s = input.trim();
+        if s.is_empty() {
+            return Err(AmountError::Empty);
+        }
+
+        if let Some(digits) = s.strip_suffix("attos").or_else(|| s.strip_suffix("atto")) {
+            let digits = digits.trim_end();
+            if digits.is_empty() || !is_digits(digits) {
+                return Err(AmountError::InvalidFormat);
+            }
+            return digits.parse::<u128>().map(Amount::from_attos).map_err(|_| AmountError::Overflow);
+        }
+
+        let (int_part, frac_part) = s.split_once('.').unwrap_or((s, ""));
+        if (int_part.is_empty() && frac_part.is_empty()) || !is_digits(int_part) || !is_digits(frac_part) {
+            return Err(AmountError::InvalidFormat);
+        }
+        if frac_part.len() > DECIMAL_PLACES {
+            return Err(AmountError::TooManyDecimals);
+        }
+        let tokens = if int_part.is_empty() { 0 } else { int_part.parse::<u128>().map_err(|_| AmountError::Overflow)? };
+        let frac = if frac_part.is_empty() { 0 } else { format!("{:0<width$}", frac_part, width = DECIMAL_PLACES).parse::<u128>().map_err(|_| AmountError::InvalidFormat)? };
+        tokens
+            .checked_mul(ATTOS_PER_TOKEN)
+            .and_then(|attos| attos.checked_add(frac))
+            .map(Amount::from_attos)
+            .ok_or(AmountError::Overflow)
+    }
+
+    /// Canonical decimal representation without trailing zeros ("1.5", "2", "0.000000000000000001")
+    pub fn format_amount(amount: Amount) -> String {
+        let attos = u128::from(amount);
+        let tokens = attos / ATTOS_PER_TOKEN;
+        let frac = attos % ATTOS_PER_TOKEN;
+        if frac == 0 {
+            return tokens.to_string();
+        }
+        let frac_str = format!("{:0width$}", frac, width = DECIMAL_PLACES);
+        format!("{}.{}", tokens, frac_str.trim_end_matches('0'))
+    }
 }
 
 pub struct DonationsAbi;
@@ -393,6 +857,38 @@ pub enum Operation {
     DeletePost {
         post_id: String,
     },
+    
+    // Re-send a tracked message that was never acknowledged
+    RetryOutstanding {
+        id: String,
+    },
+    
+    // Run one bounded batch of state migration towards target_version
+    RunMigration {
+        target_version: u32,
+    },
+    
+    // Entry point for other Linera applications (via call_application)
+    ApplicationCall {
+        call: DonationsCall,
+    },
+}
+
+// Calls other applications can make on this one. Funds are taken from the calling
+// application's own account, and the caller must be listed in `allowed_callers`.
+#[derive(Debug, Deserialize, Serialize)]
+pub enum DonationsCall {
+    Donate {
+        to: linera_sdk::abis::fungible::Account,
+        amount: Amount,
+        message: Option<String>,
+    },
+    // As with Operation::TransferToBuy, the author's account is checked when the product is known here
+    Purchase {
+        product_id: String,
+        amount: Amount,
+        author: linera_sdk::abis::fungible::Account,
+    },
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -400,4 +896,424 @@ pub enum ResponseData {
     Ok,
     Profile(Option<Profile>),
     Donations(Vec<DonationRecord>),
+    Blob(DataBlobInfo),
+    Error(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use amounts::{format_amount, parse_user_amount, AmountError};
+    use linera_sdk::linera_base_types::Account;
+
+    fn owner(n: u8) -> AccountOwner {
+        format!("0x{}", format!("{:02x}", n).repeat(32)).parse().expect("valid owner")
+    }
+
+    fn chain(n: u8) -> ChainId {
+        format!("{:02x}", n).repeat(32).parse().expect("valid chain id")
+    }
+
+    fn product(author: AccountOwner, author_chain: ChainId) -> Product {
+        Product {
+            id: "p1".to_string(),
+            author,
+            author_chain_id: author_chain.to_string(),
+            public_data: CustomFields::new(),
+            price: Amount::ONE,
+            private_data: CustomFields::new(),
+            success_message: None,
+            order_form: Vec::new(),
+            created_at: 0,
+        }
+    }
+
+    #[test]
+    fn donation_target_rejects_chain_owner_unless_allowed() {
+        let chain_account = Account { chain_id: chain(1), owner: AccountOwner::CHAIN };
+        assert!(accounts::validate_donation_target(&chain_account, false).is_err());
+        assert!(accounts::validate_donation_target(&chain_account, true).is_ok());
+
+        let user_account = Account { chain_id: chain(1), owner: owner(1) };
+        assert!(accounts::validate_donation_target(&user_account, false).is_ok());
+    }
+
+    #[test]
+    fn purchase_target_rejects_chain_owner() {
+        let chain_account = Account { chain_id: chain(1), owner: AccountOwner::CHAIN };
+        assert!(accounts::validate_purchase_target(&chain_account, None).is_err());
+        let item = product(AccountOwner::CHAIN, chain(1));
+        assert!(accounts::validate_purchase_target(&chain_account, Some(&item)).is_err());
+    }
+
+    #[test]
+    fn purchase_target_must_be_the_product_author() {
+        let item = product(owner(1), chain(1));
+        let wrong_author = Account { chain_id: chain(1), owner: owner(2) };
+        assert_eq!(
+            accounts::validate_purchase_target(&wrong_author, Some(&item)),
+            Err("Invalid target: not the product author".to_string())
+        );
+    }
+
+    #[test]
+    fn purchase_target_must_be_on_the_author_chain() {
+        let item = product(owner(1), chain(1));
+        let wrong_chain = Account { chain_id: chain(2), owner: owner(1) };
+        assert_eq!(
+            accounts::validate_purchase_target(&wrong_chain, Some(&item)),
+            Err("Invalid target: not the product author's chain".to_string())
+        );
+        let author = Account { chain_id: chain(1), owner: owner(1) };
+        assert!(accounts::validate_purchase_target(&author, Some(&item)).is_ok());
+    }
+
+    #[test]
+    fn purchase_target_for_unknown_product_only_checks_owner() {
+        // Products created on other chains may not be mirrored locally yet
+        let target = Account { chain_id: chain(2), owner: owner(2) };
+        assert!(accounts::validate_purchase_target(&target, None).is_ok());
+    }
+
+    #[test]
+    fn week_bucket_rolls_over_at_week_boundary() {
+        assert_eq!(week_bucket(0), 0);
+        assert_eq!(week_bucket(WEEK_MICROS - 1), 0);
+        assert_eq!(week_bucket(WEEK_MICROS), 1);
+        assert_eq!(week_bucket(2 * WEEK_MICROS - 1), 1);
+        assert_eq!(week_bucket(2 * WEEK_MICROS), 2);
+    }
+
+    #[test]
+    fn sanitize_strips_tags_and_collapses_whitespace() {
+        assert_eq!(sanitize_message("<b>thanks</b>   for\n\tthe <i>stream</i>"), "thanks for the stream");
+        assert_eq!(sanitize_message("<script>alert(1)</script>hi"), "alert(1)hi");
+        assert_eq!(sanitize_message("<img src=x onerror=alert(1)>gg"), "gg");
+    }
+
+    #[test]
+    fn sanitize_keeps_and_escapes_angle_brackets_outside_tags() {
+        assert_eq!(sanitize_message("I <3 this > that"), "I &lt;3 this &gt; that");
+        assert_eq!(sanitize_message("1 < 2 and 3 > 2"), "1 &lt; 2 and 3 &gt; 2");
+        assert_eq!(sanitize_message("unclosed <b tag"), "unclosed &lt;b tag");
+        assert_eq!(sanitize_message("<<b>>"), "&lt;&gt;");
+    }
+
+    #[test]
+    fn sanitize_limits_emoji_runs() {
+        assert_eq!(sanitize_message(&"🎉".repeat(8)), "🎉".repeat(MAX_EMOJI_RUN));
+        // Whitespace or text between emoji starts a new run
+        assert_eq!(sanitize_message("🎉🎉🎉 🎉🎉🎉"), "🎉🎉🎉 🎉🎉🎉");
+    }
+
+    #[test]
+    fn sanitize_counts_emoji_sequences_as_one() {
+        let family = "👨\u{200D}👩\u{200D}👧";
+        assert_eq!(sanitize_message(&family.repeat(7)), family.repeat(MAX_EMOJI_RUN));
+        let thumbs = "👍🏽";
+        assert_eq!(sanitize_message(&thumbs.repeat(6)), thumbs.repeat(MAX_EMOJI_RUN));
+        let heart = "❤\u{FE0F}";
+        assert_eq!(sanitize_message(&heart.repeat(6)), heart.repeat(MAX_EMOJI_RUN));
+        let flag = "🇺🇦";
+        assert_eq!(sanitize_message(&flag.repeat(6)), flag.repeat(MAX_EMOJI_RUN));
+    }
+
+    #[test]
+    fn split_burn_without_burn_keeps_the_full_amount() {
+        assert_eq!(split_burn(Amount::from_tokens(10), 0), (Amount::from_tokens(10), Amount::ZERO));
+    }
+
+    #[test]
+    fn split_burn_takes_the_configured_share() {
+        assert_eq!(split_burn(Amount::from_tokens(10), 100), (Amount::from_millis(9_900), Amount::from_millis(100)));
+        assert_eq!(split_burn(Amount::from_tokens(10), 2_500), (Amount::from_millis(7_500), Amount::from_millis(2_500)));
+    }
+
+    #[test]
+    fn split_burn_rounds_in_favor_of_the_recipient() {
+        // 1% of 199 attos is 1.99 attos: one atto is burned, the rest goes to the recipient
+        assert_eq!(split_burn(Amount::from_attos(199), 100), (Amount::from_attos(198), Amount::from_attos(1)));
+        assert_eq!(split_burn(Amount::from_attos(99), 100), (Amount::from_attos(99), Amount::ZERO));
+    }
+
+    #[test]
+    fn split_burn_clamps_bps_to_the_whole_amount() {
+        assert_eq!(split_burn(Amount::from_tokens(3), 10_000), (Amount::ZERO, Amount::from_tokens(3)));
+        assert_eq!(split_burn(Amount::from_tokens(3), 50_000), (Amount::ZERO, Amount::from_tokens(3)));
+    }
+
+    #[test]
+    fn split_burn_does_not_overflow_on_max_amount() {
+        let (kept, burned) = split_burn(Amount::MAX, 9_999);
+        assert_eq!(kept.saturating_add(burned), Amount::MAX);
+        assert!(kept > Amount::ZERO && burned > kept);
+        assert_eq!(split_burn(Amount::MAX, 10_000), (Amount::ZERO, Amount::MAX));
+    }
+
+    fn donation_sent(source_chain_id: Option<String>) -> DonationsEvent {
+        DonationsEvent::DonationSent {
+            id: 1,
+            from: owner(1),
+            to: owner(2),
+            amount: Amount::ONE,
+            message: None,
+            source_chain_id,
+            to_chain_id: Some(chain(2).to_string()),
+            timestamp: 0,
+        }
+    }
+
+    #[test]
+    fn donations_without_details_keep_the_original_variants() {
+        assert!(matches!(donation_sent(None).with_details(DonationDetails::default()), DonationsEvent::DonationSent { .. }));
+        let message = Message::TransferWithMessage {
+            owner: owner(2),
+            amount: Amount::ONE,
+            text_message: None,
+            source_chain_id: chain(1),
+            source_owner: owner(1),
+        };
+        assert!(matches!(message.with_details(DonationDetails::default()), Message::TransferWithMessage { .. }));
+    }
+
+    #[test]
+    fn donations_with_details_use_the_details_variants() {
+        let details = DonationDetails { burned_amount: Amount::ONE, via_application: None };
+        match donation_sent(None).with_details(details.clone()) {
+            DonationsEvent::DonationSentWithDetails { details: carried, .. } => assert_eq!(carried, details),
+            other => panic!("unexpected event {:?}", other),
+        }
+    }
+
+    #[test]
+    fn burn_is_reported_only_by_the_senders_copy() {
+        let details = DonationDetails { burned_amount: Amount::ONE, via_application: None };
+        let sender = chain(1).to_string();
+        let recipient = chain(2).to_string();
+        // Same-chain donation, and the sender's copy of a cross-chain one
+        assert_eq!(donation_sent(None).with_details(details.clone()).burned_on(&sender), Amount::ONE);
+        assert_eq!(donation_sent(Some(sender.clone())).with_details(details.clone()).burned_on(&sender), Amount::ONE);
+        // The recipient re-emits the donation with the sender's chain as source
+        assert_eq!(donation_sent(Some(sender.clone())).with_details(details).burned_on(&recipient), Amount::ZERO);
+        assert_eq!(donation_sent(None).burned_on(&sender), Amount::ZERO);
+    }
+
+    // Layouts as they were before donation details and application calls were added
+    #[derive(Serialize)]
+    struct LegacyDonationRecord {
+        id: u64,
+        timestamp: u64,
+        from: AccountOwner,
+        to: AccountOwner,
+        amount: Amount,
+        message: Option<String>,
+        source_chain_id: Option<String>,
+        to_chain_id: Option<String>,
+    }
+
+    #[derive(Serialize)]
+    struct LegacyPurchase {
+        id: String,
+        product_id: String,
+        buyer: AccountOwner,
+        buyer_chain_id: String,
+        seller: AccountOwner,
+        seller_chain_id: String,
+        amount: Amount,
+        timestamp: u64,
+        order_data: OrderResponses,
+        product: Product,
+    }
+
+    #[derive(Serialize)]
+    #[allow(dead_code)]
+    enum LegacyMessage {
+        Notify,
+        TransferWithMessage { owner: AccountOwner, amount: Amount, text_message: Option<String>, source_chain_id: ChainId, source_owner: AccountOwner },
+    }
+
+    #[derive(Serialize)]
+    #[allow(dead_code)]
+    enum LegacyEvent {
+        ProfileNameUpdated,
+        ProfileBioUpdated,
+        ProfileSocialUpdated,
+        ProfileAvatarUpdated,
+        ProfileHeaderUpdated,
+        DonationSent { id: u64, from: AccountOwner, to: AccountOwner, amount: Amount, message: Option<String>, source_chain_id: Option<String>, to_chain_id: Option<String>, timestamp: u64 },
+    }
+
+    #[test]
+    fn donation_record_keeps_its_stored_layout() {
+        let legacy = LegacyDonationRecord {
+            id: 7,
+            timestamp: 42,
+            from: owner(1),
+            to: owner(2),
+            amount: Amount::ONE,
+            message: Some("gg".to_string()),
+            source_chain_id: Some(chain(1).to_string()),
+            to_chain_id: None,
+        };
+        let bytes = bcs::to_bytes(&legacy).unwrap();
+        let decoded: DonationRecord = bcs::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.message.as_deref(), Some("gg"));
+        assert_eq!(decoded.burned_amount, Amount::ZERO);
+        assert_eq!(decoded.via_application, None);
+        // Read-time fields never reach storage
+        let hydrated = DonationRecord {
+            burned_amount: Amount::ONE,
+            message_sanitized: Some("gg".to_string()),
+            via_application: Some("app".to_string()),
+            ..decoded
+        };
+        assert_eq!(bcs::to_bytes(&hydrated).unwrap(), bytes);
+    }
+
+    #[test]
+    fn purchase_keeps_its_stored_layout() {
+        let legacy = LegacyPurchase {
+            id: "purchase-1".to_string(),
+            product_id: "p1".to_string(),
+            buyer: owner(1),
+            buyer_chain_id: chain(1).to_string(),
+            seller: owner(2),
+            seller_chain_id: chain(2).to_string(),
+            amount: Amount::ONE,
+            timestamp: 42,
+            order_data: OrderResponses::new(),
+            product: product(owner(2), chain(2)),
+        };
+        let bytes = bcs::to_bytes(&legacy).unwrap();
+        let decoded: Purchase = bcs::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.via_application, None);
+        let hydrated = Purchase { via_application: Some("app".to_string()), ..decoded };
+        assert_eq!(bcs::to_bytes(&hydrated).unwrap(), bytes);
+    }
+
+    #[test]
+    fn plain_donation_message_and_event_encode_as_before() {
+        let message = Message::TransferWithMessage {
+            owner: owner(2),
+            amount: Amount::ONE,
+            text_message: Some("gg".to_string()),
+            source_chain_id: chain(1),
+            source_owner: owner(1),
+        }
+        .with_details(DonationDetails::default());
+        let legacy_message = LegacyMessage::TransferWithMessage {
+            owner: owner(2),
+            amount: Amount::ONE,
+            text_message: Some("gg".to_string()),
+            source_chain_id: chain(1),
+            source_owner: owner(1),
+        };
+        assert_eq!(bcs::to_bytes(&message).unwrap(), bcs::to_bytes(&legacy_message).unwrap());
+
+        let event = donation_sent(Some(chain(1).to_string())).with_details(DonationDetails::default());
+        let legacy_event = LegacyEvent::DonationSent {
+            id: 1,
+            from: owner(1),
+            to: owner(2),
+            amount: Amount::ONE,
+            message: None,
+            source_chain_id: Some(chain(1).to_string()),
+            to_chain_id: Some(chain(2).to_string()),
+            timestamp: 0,
+        };
+        assert_eq!(bcs::to_bytes(&event).unwrap(), bcs::to_bytes(&legacy_event).unwrap());
+    }
+
+    #[test]
+    fn direct_orders_keep_the_original_message() {
+        let order = |via_application: Option<String>| {
+            Message::OrderReceived {
+                purchase_id: "purchase-1".to_string(),
+                product_id: "p1".to_string(),
+                buyer: owner(1),
+                buyer_chain_id: chain(1),
+                amount: Amount::ONE,
+                order_data: OrderResponses::new(),
+                timestamp: 0,
+            }
+            .with_application(via_application)
+        };
+        assert!(matches!(order(None), Message::OrderReceived { .. }));
+        assert!(matches!(order(Some("app".to_string())), Message::OrderReceivedViaApplication { via_application, .. } if via_application == "app"));
+    }
+
+    const MAX_ATTOS: &str = "340282366920938463463374607431768211455";
+    const MAX_TOKENS: &str = "340282366920938463463.374607431768211455";
+
+    #[test]
+    fn parse_accepts_partial_decimals() {
+        assert_eq!(parse_user_amount(".5"), Ok(Amount::from_millis(500)));
+        assert_eq!(parse_user_amount("1."), Ok(Amount::ONE));
+        assert_eq!(parse_user_amount("0.25"), Ok(Amount::from_millis(250)));
+        assert_eq!(parse_user_amount("  2  "), Ok(Amount::from_tokens(2)));
+        assert_eq!(parse_user_amount("."), Err(AmountError::InvalidFormat));
+        assert_eq!(parse_user_amount(""), Err(AmountError::Empty));
+        assert_eq!(parse_user_amount("   "), Err(AmountError::Empty));
+    }
+
+    #[test]
+    fn parse_allows_at_most_18_decimals() {
+        assert_eq!(parse_user_amount("0.000000000000000001"), Ok(Amount::from_attos(1)));
+        assert_eq!(parse_user_amount("1.123456789012345678"), Ok(Amount::from_attos(1_123_456_789_012_345_678)));
+        assert_eq!(parse_user_amount("0.0000000000000000001"), Err(AmountError::TooManyDecimals));
+        assert_eq!(parse_user_amount("1.1234567890123456780"), Err(AmountError::TooManyDecimals));
+    }
+
+    #[test]
+    fn parse_reads_atto_suffixes_as_integers() {
+        assert_eq!(parse_user_amount("250atto"), Ok(Amount::from_attos(250)));
+        assert_eq!(parse_user_amount("250attos"), Ok(Amount::from_attos(250)));
+        assert_eq!(parse_user_amount("250 attos"), Ok(Amount::from_attos(250)));
+        assert_eq!(parse_user_amount("atto"), Err(AmountError::InvalidFormat));
+        assert_eq!(parse_user_amount("1.5attos"), Err(AmountError::InvalidFormat));
+        assert_eq!(parse_user_amount("-5attos"), Err(AmountError::InvalidFormat));
+    }
+
+    #[test]
+    fn parse_reports_overflow_on_both_paths() {
+        assert_eq!(parse_user_amount(&format!("{}attos", MAX_ATTOS)), Ok(Amount::MAX));
+        assert_eq!(parse_user_amount("340282366920938463463374607431768211456attos"), Err(AmountError::Overflow));
+        assert_eq!(parse_user_amount(MAX_TOKENS), Ok(Amount::MAX));
+        // One atto above the maximum, a whole token above it, and an integer part beyond u128
+        assert_eq!(parse_user_amount("340282366920938463463.374607431768211456"), Err(AmountError::Overflow));
+        assert_eq!(parse_user_amount("340282366920938463464"), Err(AmountError::Overflow));
+        assert_eq!(parse_user_amount(&format!("{}0", MAX_ATTOS)), Err(AmountError::Overflow));
+    }
+
+    #[test]
+    fn parse_rejects_signs_and_exponents() {
+        for input in ["-1", "-0.5", "+1", "1e18", "1E3", "1.5e-3", "1,5", "1.2.3", "0x10", "one"] {
+            assert_eq!(parse_user_amount(input), Err(AmountError::InvalidFormat), "{}", input);
+        }
+    }
+
+    #[test]
+    fn format_amount_is_canonical() {
+        assert_eq!(format_amount(Amount::ZERO), "0");
+        assert_eq!(format_amount(Amount::from_tokens(2)), "2");
+        assert_eq!(format_amount(Amount::from_millis(1_500)), "1.5");
+        assert_eq!(format_amount(Amount::from_attos(1)), "0.000000000000000001");
+        assert_eq!(format_amount(Amount::MAX), MAX_TOKENS);
+    }
+
+    #[test]
+    fn format_amount_round_trips_through_parse() {
+        let amounts = [
+            Amount::ZERO,
+            Amount::from_attos(1),
+            Amount::from_millis(500),
+            Amount::ONE,
+            Amount::from_attos(1_123_456_789_012_345_678),
+            Amount::from_tokens(1_000_000),
+            Amount::MAX,
+        ];
+        for amount in amounts {
+            assert_eq!(parse_user_amount(&format_amount(amount)), Ok(amount));
+        }
+    }
 }
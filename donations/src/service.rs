@@ -8,8 +8,11 @@ use linera_sdk::{linera_base_types::{AccountOwner, WithServiceAbi, Amount}, view
 use donations::{
     DonationsAbi, Operation, AccountInput, Profile as LibProfile, DonationRecord as LibDonationRecord,
     ProfileView, DonationView, SocialLinkInput, TotalAmountView, CustomFields, OrderFormField,
-    OrderFormFieldInput, OrderResponses, Product, ContentSubscription, Post,
+    OrderFormFieldInput, OrderResponses, Product, Purchase, ContentSubscription, Post, ActivityItem, ActivityKind,
+    DataBlobInfo, RevenueBucket, CURRENT_STATE_VERSION, OutstandingMessage, OUTSTANDING_TIMEOUT_MICROS, ChainActivity,
+    RegisteredChainView,
 };
+use donations::amounts::{parse_user_amount, format_amount, AmountError};
 use state::DonationsState;
 use async_graphql::{SimpleObject, InputObject};
 
@@ -70,6 +73,37 @@ struct PurchaseFullView {
     product: ProductFullView,
 }
 
+// Result of validating a user-entered amount
+#[derive(SimpleObject)]
+struct ParsedAmountView {
+    input: String,
+    amount: Option<Amount>,
+    formatted: Option<String>,
+    error: Option<AmountError>,
+    error_message: Option<String>,
+}
+
+// Buyer entry of a product's purchase list (author view only)
+#[derive(SimpleObject)]
+struct ProductBuyerView {
+    purchase_id: String,
+    buyer: AccountOwner,
+    buyer_chain_id: String,
+    amount: Amount,
+    timestamp: u64,
+    order_data: Vec<KeyValuePair>,
+}
+
+// Purchases of a single product: aggregates for everyone, buyers for the author only
+#[derive(SimpleObject)]
+struct ProductPurchasesView {
+    product_id: String,
+    total_count: u64,
+    total_amount: Amount,
+    buyers_visible: bool,
+    buyers: Vec<ProductBuyerView>,
+}
+
 // Helper functions
 fn btree_to_pairs(map: &CustomFields) -> Vec<KeyValuePair> {
     map.iter().map(|(k, v)| KeyValuePair { key: k.clone(), value: v.clone() }).collect()
@@ -110,6 +144,35 @@ fn product_to_full_view(p: &Product) -> ProductFullView {
     }
 }
 
+const MIGRATION_REQUIRED: &str = "migration required: run the runMigration mutation until stateVersion is current";
+
+// Amount inputs are rejected with a GraphQL error instead of silently becoming zero
+fn parse_amount_input(input: &str) -> async_graphql::Result<Amount> {
+    parse_user_amount(input).map_err(|e| async_graphql::Error::new(format!("Invalid amount '{}': {}", input, e)))
+}
+
+// Buyer identities stay on the product author's chain, which is where sellers receive
+// their orders. The main chain mirrors every sale but is queried by every frontend,
+// so it only serves aggregates.
+fn buyers_visible(current_chain_id: &str, author_chain_id: Option<&str>) -> bool {
+    author_chain_id == Some(current_chain_id)
+}
+
+fn purchase_to_full_view(pur: &Purchase) -> PurchaseFullView {
+    PurchaseFullView {
+        id: pur.id.clone(),
+        product_id: pur.product_id.clone(),
+        buyer: pur.buyer,
+        buyer_chain_id: pur.buyer_chain_id.clone(),
+        seller: pur.seller,
+        seller_chain_id: pur.seller_chain_id.clone(),
+        amount: pur.amount,
+        timestamp: pur.timestamp,
+        order_data: btree_to_pairs(&pur.order_data),
+        product: product_to_full_view(&pur.product),
+    }
+}
+
 linera_sdk::service!(DonationsService);
 
 pub struct DonationsService { runtime: Arc<ServiceRuntime<Self>> }
@@ -200,7 +263,7 @@ impl QueryRoot {
                     Ok(ids) => {
                         let mut res = Vec::new();
                         for id in ids {
-                            if let Ok(Some(r)) = state.donations.get(&id).await { res.push(r); }
+                            if let Ok(Some(r)) = state.get_donation(id).await { res.push(r); }
                         }
                         res
                     },
@@ -275,7 +338,8 @@ impl QueryRoot {
                                 to_owner: r.to,
                                 to_chain_id: to_chain_id.clone(),
                                 amount: r.amount,
-                                message: r.message,
+                                message: r.message_sanitized,
+                                raw_message: r.message,
                             });
                         }
                         res
@@ -304,7 +368,8 @@ impl QueryRoot {
                                 to_owner: r.to,
                                 to_chain_id,
                                 amount: r.amount,
-                                message: r.message,
+                                message: r.message_sanitized,
+                                raw_message: r.message,
                             });
                         }
                         res
@@ -323,7 +388,7 @@ impl QueryRoot {
                     Ok(ids) => {
                         let mut res = Vec::new();
                         for id in ids {
-                            if let Ok(Some(r)) = state.donations.get(&id).await {
+                            if let Ok(Some(r)) = state.get_donation(id).await {
                                 let from_chain_id = match r.source_chain_id.clone() {
                                     Some(id) => id,
                                     None => state.subscriptions.get(&r.from).await.ok().flatten().unwrap_or_else(|| self.runtime.chain_id().to_string())
@@ -332,7 +397,7 @@ impl QueryRoot {
                                     Some(id) => id,
                                     None => state.subscriptions.get(&r.to).await.ok().flatten().unwrap_or_else(|| self.runtime.chain_id().to_string())
                                 };
-                                res.push(DonationView { id: r.id, timestamp: r.timestamp, from_owner: r.from, from_chain_id, to_owner: r.to, to_chain_id, amount: r.amount, message: r.message });
+                                res.push(DonationView { id: r.id, timestamp: r.timestamp, from_owner: r.from, from_chain_id, to_owner: r.to, to_chain_id, amount: r.amount, message: r.message_sanitized, raw_message: r.message });
                             }
                         }
                         res
@@ -380,6 +445,14 @@ impl QueryRoot {
         }
     }
 
+    /// Total amount burned by donations made on this chain (on the main chain: by every registered chain)
+    async fn total_burned(&self) -> Amount {
+        match DonationsState::load(self.storage_context.clone()).await {
+            Ok(state) => *state.total_burned.get(),
+            Err(_) => Amount::ZERO,
+        }
+    }
+
     async fn total_received_view(&self, owner: AccountOwner) -> TotalAmountView {
         match DonationsState::load(self.storage_context.clone()).await {
             Ok(state) => {
@@ -416,6 +489,68 @@ impl QueryRoot {
         }
     }
 
+    /// Get a creator's activity feed: donations received, products published and sales, newest first
+    /// Pass the last item's timestamp and id as the cursor to fetch the next page
+    async fn activity_feed(&self, owner: AccountOwner, limit: u32, before_timestamp: Option<u64>, before_id: Option<String>) -> Vec<ActivityItem> {
+        match DonationsState::load(self.storage_context.clone()).await {
+            Ok(state) => {
+                let mut items = Vec::new();
+                for r in state.list_donations_by_recipient(owner).await.unwrap_or_default() {
+                    items.push(ActivityItem {
+                        id: format!("donation-{}", r.id),
+                        kind: ActivityKind::DonationReceived,
+                        timestamp: r.timestamp,
+                        amount: Some(r.amount),
+                        counterparty: Some(r.from),
+                        counterparty_chain_id: r.source_chain_id,
+                        message: r.message_sanitized,
+                        product_id: None,
+                        purchase_id: None,
+                    });
+                }
+                for p in state.list_products_by_author(owner).await.unwrap_or_default() {
+                    items.push(ActivityItem {
+                        id: format!("product-{}", p.id),
+                        kind: ActivityKind::ProductPublished,
+                        timestamp: p.created_at,
+                        amount: Some(p.price),
+                        counterparty: None,
+                        counterparty_chain_id: None,
+                        message: None,
+                        product_id: Some(p.id),
+                        purchase_id: None,
+                    });
+                }
+                for pur in state.list_purchases_by_seller(owner).await.unwrap_or_default() {
+                    items.push(ActivityItem {
+                        id: format!("sale-{}", pur.id),
+                        kind: ActivityKind::ProductSold,
+                        timestamp: pur.timestamp,
+                        amount: Some(pur.amount),
+                        counterparty: Some(pur.buyer),
+                        counterparty_chain_id: Some(pur.buyer_chain_id),
+                        message: None,
+                        product_id: Some(pur.product_id),
+                        purchase_id: Some(pur.id),
+                    });
+                }
+                
+                // Sort by (timestamp, id) descending so pages never overlap or skip same-timestamp items
+                items.sort_by(|a, b| b.timestamp.cmp(&a.timestamp).then_with(|| b.id.cmp(&a.id)));
+                // Older chains may have indexed the same sale twice (message and mirrored event)
+                items.dedup_by(|a, b| a.id == b.id);
+                items.into_iter()
+                    .filter(|item| match before_timestamp {
+                        Some(ts) => item.timestamp < ts || (item.timestamp == ts && before_id.as_ref().is_some_and(|id| item.id < *id)),
+                        None => true,
+                    })
+                    .take(limit as usize)
+                    .collect()
+            },
+            Err(_) => Vec::new(),
+        }
+    }
+
     // Marketplace queries - NEW: Using flexible product structure
     
     /// Get list of all author subscription offers (for indexer)
@@ -514,24 +649,21 @@ impl QueryRoot {
 
     /// Get purchases for buyer with full product data
     async fn purchases(&self, owner: AccountOwner) -> Vec<PurchaseFullView> {
+        self.buyer_purchases(owner).await
+    }
+
+    /// Get purchases for buyer (alias for purchases)
+    async fn my_purchases(&self, owner: AccountOwner) -> Vec<PurchaseFullView> {
+        self.buyer_purchases(owner).await
+    }
+
+    /// Get all orders received by seller (for "My Orders" tab)
+    async fn my_orders(&self, owner: AccountOwner) -> Vec<PurchaseFullView> {
         match DonationsState::load(self.storage_context.clone()).await {
             Ok(state) => {
-                match state.list_purchases_by_buyer(owner).await {
+                match state.list_purchases_by_seller(owner).await {
                     Ok(purchases) => {
-                        purchases.into_iter().map(|pur| {
-                            PurchaseFullView {
-                                id: pur.id,
-                                product_id: pur.product_id,
-                                buyer: pur.buyer,
-                                buyer_chain_id: pur.buyer_chain_id,
-                                seller: pur.seller,
-                                seller_chain_id: pur.seller_chain_id,
-                                amount: pur.amount,
-                                timestamp: pur.timestamp,
-                                order_data: btree_to_pairs(&pur.order_data),
-                                product: product_to_full_view(&pur.product),
-                            }
-                        }).collect()
+                        purchases.iter().map(purchase_to_full_view).collect()
                     },
                     Err(_) => Vec::new(),
                 }
@@ -540,26 +672,66 @@ impl QueryRoot {
         }
     }
 
-    /// Get purchases for buyer (alias for purchases)
-    async fn my_purchases(&self, owner: AccountOwner) -> Vec<PurchaseFullView> {
+    /// Get purchases of a product. Buyer identities and order data are only returned
+    /// when queried on the product author's chain; elsewhere only totals are filled in
+    async fn product_purchases(&self, product_id: String, limit: u32, offset: u32) -> async_graphql::Result<ProductPurchasesView> {
+        let mut view = ProductPurchasesView { product_id: product_id.clone(), total_count: 0, total_amount: Amount::ZERO, buyers_visible: false, buyers: Vec::new() };
+        let state = match DonationsState::load(self.storage_context.clone()).await {
+            Ok(state) => state,
+            Err(_) => return Ok(view),
+        };
+        if state.migration_required().await.unwrap_or(true) {
+            return Err(MIGRATION_REQUIRED.into());
+        }
+        let purchases = state.list_purchases_by_product(&product_id).await.unwrap_or_default();
+        view.total_count = purchases.len() as u64;
+        view.total_amount = purchases.iter().fold(Amount::ZERO, |sum, p| sum.saturating_add(p.amount));
+        
+        // Prefer the live product; fall back to the snapshot stored with the purchase
+        let author_chain_id = match state.get_product(&product_id).await {
+            Ok(Some(p)) => Some(p.author_chain_id),
+            _ => purchases.first().map(|p| p.product.author_chain_id.clone()),
+        };
+        view.buyers_visible = buyers_visible(&self.runtime.chain_id().to_string(), author_chain_id.as_deref());
+        
+        if view.buyers_visible {
+            view.buyers = purchases.into_iter()
+                .skip(offset as usize)
+                .take(limit as usize)
+                .map(|pur| ProductBuyerView {
+                    purchase_id: pur.id,
+                    buyer: pur.buyer,
+                    buyer_chain_id: pur.buyer_chain_id,
+                    amount: pur.amount,
+                    timestamp: pur.timestamp,
+                    order_data: btree_to_pairs(&pur.order_data),
+                })
+                .collect();
+        }
+        Ok(view)
+    }
+
+    /// List registered chains with their activity counters (main chain explorer)
+    async fn registered_chains(&self, limit: u32, offset: u32) -> Vec<RegisteredChainView> {
         match DonationsState::load(self.storage_context.clone()).await {
             Ok(state) => {
-                match state.list_purchases_by_buyer(owner).await {
-                    Ok(purchases) => {
-                        purchases.into_iter().map(|pur| {
-                            PurchaseFullView {
-                                id: pur.id,
-                                product_id: pur.product_id,
-                                buyer: pur.buyer,
-                                buyer_chain_id: pur.buyer_chain_id,
-                                seller: pur.seller,
-                                seller_chain_id: pur.seller_chain_id,
-                                amount: pur.amount,
-                                timestamp: pur.timestamp,
-                                order_data: btree_to_pairs(&pur.order_data),
-                                product: product_to_full_view(&pur.product),
+                match state.subscriptions.indices().await {
+                    Ok(owners) => {
+                        let mut res = Vec::new();
+                        for owner in owners.into_iter().skip(offset as usize).take(limit as usize) {
+                            if let Ok(Some(chain_id)) = state.subscriptions.get(&owner).await {
+                                let profile_name = state.get_profile(owner).await.ok().flatten().map(|p| p.name);
+                                let activity = state.get_chain_activity(&chain_id).await.ok().flatten().unwrap_or_default();
+                                res.push(RegisteredChainView {
+                                    chain_id,
+                                    owner,
+                                    profile_name,
+                                    donation_count: activity.donation_count,
+                                    last_event_at: activity.last_event_at,
+                                });
                             }
-                        }).collect()
+                        }
+                        res
                     },
                     Err(_) => Vec::new(),
                 }
@@ -568,27 +740,22 @@ impl QueryRoot {
         }
     }
 
-    /// Get all orders received by seller (for "My Orders" tab)
-    async fn my_orders(&self, owner: AccountOwner) -> Vec<PurchaseFullView> {
+    /// Get activity counters for a single chain
+    async fn chain_activity(&self, chain_id: String) -> Option<ChainActivity> {
+        match DonationsState::load(self.storage_context.clone()).await {
+            Ok(state) => state.get_chain_activity(&chain_id).await.ok().flatten(),
+            Err(_) => None,
+        }
+    }
+
+    /// Get outbound messages still unacknowledged after the timeout (defaults to 5 minutes)
+    async fn outstanding_messages(&self, timeout_micros: Option<u64>) -> Vec<OutstandingMessage> {
         match DonationsState::load(self.storage_context.clone()).await {
             Ok(state) => {
-                match state.list_purchases_by_seller(owner).await {
-                    Ok(purchases) => {
-                        purchases.into_iter().map(|pur| {
-                            PurchaseFullView {
-                                id: pur.id,
-                                product_id: pur.product_id,
-                                buyer: pur.buyer,
-                                buyer_chain_id: pur.buyer_chain_id,
-                                seller: pur.seller,
-                                seller_chain_id: pur.seller_chain_id,
-                                amount: pur.amount,
-                                timestamp: pur.timestamp,
-                                order_data: btree_to_pairs(&pur.order_data),
-                                product: product_to_full_view(&pur.product),
-                            }
-                        }).collect()
-                    },
+                let now = self.runtime.system_time().micros();
+                let timeout = timeout_micros.unwrap_or(OUTSTANDING_TIMEOUT_MICROS);
+                match state.list_outstanding().await {
+                    Ok(list) => list.into_iter().filter(|m| m.is_overdue(now, timeout)).collect(),
                     Err(_) => Vec::new(),
                 }
             },
@@ -596,6 +763,41 @@ impl QueryRoot {
         }
     }
 
+    /// Validate an amount before submitting it: returns the canonical value or a structured error
+    async fn parse_amount(&self, input: String) -> ParsedAmountView {
+        match parse_user_amount(&input) {
+            Ok(amount) => ParsedAmountView { input, amount: Some(amount), formatted: Some(format_amount(amount)), error: None, error_message: None },
+            Err(e) => ParsedAmountView { input, amount: None, formatted: None, error: Some(e), error_message: Some(e.to_string()) },
+        }
+    }
+
+    /// Get weekly revenue for a seller between two week buckets (inclusive), zero-filled
+    /// Week buckets are weeks since the Unix epoch; at most 520 weeks are returned
+    async fn revenue_series(&self, owner: AccountOwner, from_week: u64, to_week: u64) -> async_graphql::Result<Vec<RevenueBucket>> {
+        match DonationsState::load(self.storage_context.clone()).await {
+            Ok(state) => {
+                if state.migration_required().await.unwrap_or(true) {
+                    return Err(MIGRATION_REQUIRED.into());
+                }
+                Ok(state.revenue_series(owner, from_week, to_week).await.unwrap_or_default())
+            }
+            Err(_) => Ok(Vec::new()),
+        }
+    }
+
+    /// State layout version stored on this chain (see stateVersionCurrent for the expected one)
+    async fn state_version(&self) -> u32 {
+        match DonationsState::load(self.storage_context.clone()).await {
+            Ok(state) => *state.state_version.get(),
+            Err(_) => 0,
+        }
+    }
+
+    /// State layout version expected by the deployed bytecode
+    async fn state_version_current(&self) -> u32 {
+        CURRENT_STATE_VERSION
+    }
+
     /// Get all purchases in the system (for debugging)
     async fn all_purchases(&self) -> Vec<PurchaseFullView> {
         match DonationsState::load(self.storage_context.clone()).await {
@@ -604,19 +806,8 @@ impl QueryRoot {
                     Ok(ids) => {
                         let mut res = Vec::new();
                         for id in ids {
-                            if let Ok(Some(pur)) = state.purchases.get(&id).await {
-                                res.push(PurchaseFullView {
-                                    id: pur.id,
-                                    product_id: pur.product_id,
-                                    buyer: pur.buyer,
-                                    buyer_chain_id: pur.buyer_chain_id,
-                                    seller: pur.seller,
-                                    seller_chain_id: pur.seller_chain_id,
-                                    amount: pur.amount,
-                                    timestamp: pur.timestamp,
-                                    order_data: btree_to_pairs(&pur.order_data),
-                                    product: product_to_full_view(&pur.product),
-                                });
+                            if let Ok(Some(pur)) = state.get_purchase(&id).await {
+                                res.push(purchase_to_full_view(&pur));
                             }
                         }
                         res
@@ -642,6 +833,21 @@ impl QueryRoot {
             Err(_) => None,
         }
     }
+
+    /// Get the hash and size of a data blob without returning its content
+    /// Returns None if the hash is invalid
+    async fn data_blob_info(&self, hash: String) -> Option<DataBlobInfo> {
+        use linera_sdk::linera_base_types::{CryptoHash, DataBlobHash};
+        use std::str::FromStr;
+        
+        match CryptoHash::from_str(&hash) {
+            Ok(crypto_hash) => {
+                let data = self.runtime.read_data_blob(DataBlobHash(crypto_hash));
+                Some(DataBlobInfo { hash, size: data.len() as u64 })
+            }
+            Err(_) => None,
+        }
+    }
     
     // Content subscription queries
     
@@ -756,17 +962,37 @@ impl QueryRoot {
     }
 }
 
+// Shared resolver implementations (not exposed in the schema)
+impl QueryRoot {
+    async fn buyer_purchases(&self, owner: AccountOwner) -> Vec<PurchaseFullView> {
+        match DonationsState::load(self.storage_context.clone()).await {
+            Ok(state) => {
+                match state.list_purchases_by_buyer(owner).await {
+                    Ok(purchases) => purchases.iter().map(purchase_to_full_view).collect(),
+                    Err(_) => Vec::new(),
+                }
+            },
+            Err(_) => Vec::new(),
+        }
+    }
+}
+
 struct MutationRoot { runtime: Arc<ServiceRuntime<DonationsService>> }
 
 #[Object]
 impl MutationRoot {
-    async fn transfer(&self, owner: AccountOwner, amount: String, target_account: AccountInput, text_message: Option<String>) -> String {
+    async fn transfer(&self, owner: AccountOwner, amount: String, target_account: AccountInput, text_message: Option<String>) -> async_graphql::Result<String> {
+        let amount = parse_amount_input(&amount)?;
         let fungible_account = linera_sdk::abis::fungible::Account { chain_id: target_account.chain_id, owner: target_account.owner };
-        self.runtime.schedule_operation(&Operation::Transfer { owner, amount: amount.parse::<Amount>().unwrap_or_default(), target_account: fungible_account, text_message });
-        "ok".to_string()
+        self.runtime.schedule_operation(&Operation::Transfer { owner, amount, target_account: fungible_account, text_message });
+        Ok("ok".to_string())
     }
     async fn withdraw(&self) -> String { self.runtime.schedule_operation(&Operation::Withdraw); "ok".to_string() }
-    async fn mint(&self, owner: AccountOwner, amount: String) -> String { self.runtime.schedule_operation(&Operation::Mint { owner, amount: amount.parse::<Amount>().unwrap_or_default() }); "ok".to_string() }
+    async fn mint(&self, owner: AccountOwner, amount: String) -> async_graphql::Result<String> {
+        let amount = parse_amount_input(&amount)?;
+        self.runtime.schedule_operation(&Operation::Mint { owner, amount });
+        Ok("ok".to_string())
+    }
     async fn update_profile(&self, name: Option<String>, bio: Option<String>, socials: Vec<SocialLinkInput>, avatar_hash: Option<String>, header_hash: Option<String>) -> String { self.runtime.schedule_operation(&Operation::UpdateProfile { name, bio, socials, avatar_hash, header_hash }); "ok".to_string() }
     async fn register(&self, main_chain_id: String, name: Option<String>, bio: Option<String>, socials: Vec<SocialLinkInput>, avatar_hash: Option<String>, header_hash: Option<String>) -> String {
         let chain_id = main_chain_id.parse().unwrap();
@@ -794,8 +1020,8 @@ impl MutationRoot {
         private_data: Vec<KeyValueInput>,
         success_message: Option<String>,
         order_form: Vec<OrderFormFieldInputGql>,
-    ) -> String {
-        let amount = price.parse::<Amount>().unwrap_or_default();
+    ) -> async_graphql::Result<String> {
+        let amount = parse_amount_input(&price)?;
         
         // Convert input vectors to BTreeMaps
         let public_data_map: CustomFields = public_data.into_iter().map(|kv| (kv.key, kv.value)).collect();
@@ -814,7 +1040,7 @@ impl MutationRoot {
             success_message,
             order_form: order_form_list,
         });
-        "ok".to_string()
+        Ok("ok".to_string())
     }
 
     /// Update an existing product
@@ -826,8 +1052,8 @@ impl MutationRoot {
         private_data: Option<Vec<KeyValueInput>>,
         success_message: Option<String>,
         order_form: Option<Vec<OrderFormFieldInputGql>>,
-    ) -> String {
-        let price_amount = price.and_then(|p| p.parse::<Amount>().ok());
+    ) -> async_graphql::Result<String> {
+        let price_amount = price.as_deref().map(parse_amount_input).transpose()?;
         let public_data_map = public_data.map(|v| v.into_iter().map(|kv| (kv.key, kv.value)).collect());
         let private_data_map = private_data.map(|v| v.into_iter().map(|kv| (kv.key, kv.value)).collect());
         let order_form_list = order_form.map(|v| v.into_iter().map(|f| OrderFormFieldInput {
@@ -845,7 +1071,7 @@ impl MutationRoot {
             success_message,
             order_form: order_form_list,
         });
-        "ok".to_string()
+        Ok("ok".to_string())
     }
 
     async fn delete_product(&self, product_id: String) -> String {
@@ -861,23 +1087,26 @@ impl MutationRoot {
         amount: String,
         target_account: AccountInput,
         order_data: Vec<KeyValueInput>,
-    ) -> String {
+    ) -> async_graphql::Result<String> {
+        let amount = parse_amount_input(&amount)?;
         let fungible_account = linera_sdk::abis::fungible::Account { chain_id: target_account.chain_id, owner: target_account.owner };
         let order_data_map: OrderResponses = order_data.into_iter().map(|kv| (kv.key, kv.value)).collect();
         
         self.runtime.schedule_operation(&Operation::TransferToBuy {
             owner,
             product_id,
-            amount: amount.parse::<Amount>().unwrap_or_default(),
+            amount,
             target_account: fungible_account,
             order_data: order_data_map,
         });
-        "ok".to_string()
+        Ok("ok".to_string())
     }
 
     /// Schedule reading a data blob by its hash
     /// The hash should be a hex-encoded string of the blob hash (64 characters)
     /// Data blobs must be created externally via CLI `linera publish-data-blob` or GraphQL `publishDataBlob`
+    /// The operation runs in a later block, so its result is not returned here; use the
+    /// `dataBlobInfo` query to get the hash and size immediately
     async fn read_data_blob(&self, hash: String) -> String {
         self.runtime.schedule_operation(&Operation::ReadDataBlob { hash: hash.clone() });
         format!("Data blob read scheduled for hash: {}", hash)
@@ -886,10 +1115,10 @@ impl MutationRoot {
     // Content subscription mutations
     
     /// Set subscription price with description for author's content
-    async fn set_subscription_price(&self, price: String, description: Option<String>) -> String {
-        let amount = price.parse::<Amount>().unwrap_or_default();
+    async fn set_subscription_price(&self, price: String, description: Option<String>) -> async_graphql::Result<String> {
+        let amount = parse_amount_input(&price)?;
         self.runtime.schedule_operation(&Operation::SetSubscriptionPrice { price: amount, description });
-        "ok".to_string()
+        Ok("ok".to_string())
     }
     
     /// Delete/disable subscription for author's content
@@ -904,19 +1133,19 @@ impl MutationRoot {
         owner: AccountOwner,
         amount: String,
         target_account: AccountInput,
-    ) -> String {
+    ) -> async_graphql::Result<String> {
         let fungible_account = linera_sdk::abis::fungible::Account { 
             chain_id: target_account.chain_id, 
             owner: target_account.owner 
         };
-        let payment = amount.parse::<Amount>().unwrap_or_default();
+        let payment = parse_amount_input(&amount)?;
         
         self.runtime.schedule_operation(&Operation::SubscribeToAuthor {
             owner,
             amount: payment,
             target_account: fungible_account,
         });
-        "ok".to_string()
+        Ok("ok".to_string())
     }
     
     /// Create a new post (will be sent to active subscribers)
@@ -956,6 +1185,18 @@ impl MutationRoot {
         self.runtime.schedule_operation(&Operation::DeletePost { post_id });
         "ok".to_string()
     }
+    
+    /// Run one batch of the state migration; repeat until stateVersion equals stateVersionCurrent
+    async fn run_migration(&self, target_version: u32) -> String {
+        self.runtime.schedule_operation(&Operation::RunMigration { target_version });
+        "ok".to_string()
+    }
+    
+    /// Re-send an unacknowledged outbound message
+    async fn retry_outstanding(&self, id: String) -> String {
+        self.runtime.schedule_operation(&Operation::RetryOutstanding { id });
+        "ok".to_string()
+    }
 }
 
 // Input types for GraphQL mutations
@@ -972,3 +1213,193 @@ struct OrderFormFieldInputGql {
     field_type: String,
     required: bool,
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use async_graphql::{EmptyMutation, EmptySubscription, Object, Request, Schema};
+    use donations::{OrderFormField, Product, Purchase, CURRENT_STATE_VERSION};
+    use linera_sdk::{
+        linera_base_types::{AccountOwner, Amount, ChainId},
+        util::BlockingWait,
+        views::{RootView, View},
+        Service, ServiceRuntime,
+    };
+    use serde_json::json;
+
+    use super::{btree_to_pairs, buyers_visible, product_to_full_view, purchase_to_full_view, DonationsService, DonationsState, PurchaseFullView};
+
+    const AUTHOR_CHAIN: &str = "aa";
+    const MAIN_CHAIN: &str = "bb";
+
+    #[test]
+    fn buyers_are_visible_on_the_author_chain() {
+        assert!(buyers_visible(AUTHOR_CHAIN, Some(AUTHOR_CHAIN)));
+    }
+
+    #[test]
+    fn buyers_are_hidden_on_other_chains() {
+        // Includes the main chain, which mirrors every purchase but serves every frontend
+        assert!(!buyers_visible(MAIN_CHAIN, Some(AUTHOR_CHAIN)));
+        assert!(!buyers_visible("cc", Some(AUTHOR_CHAIN)));
+    }
+
+    #[test]
+    fn buyers_are_hidden_when_the_author_chain_is_unknown() {
+        assert!(!buyers_visible(MAIN_CHAIN, None));
+    }
+
+    fn owner(n: u8) -> AccountOwner {
+        format!("0x{}", format!("{:02x}", n).repeat(32)).parse().expect("valid owner")
+    }
+
+    fn sample_purchase() -> Purchase {
+        let product = Product {
+            id: "p1".to_string(),
+            author: owner(2),
+            author_chain_id: "seller-chain".to_string(),
+            public_data: BTreeMap::from([("name".to_string(), "Sketch pack".to_string())]),
+            price: Amount::from_millis(1_500),
+            private_data: BTreeMap::from([("link".to_string(), "https://example.com/pack".to_string())]),
+            success_message: Some("Thanks!".to_string()),
+            order_form: vec![OrderFormField {
+                key: "email".to_string(),
+                label: "Email".to_string(),
+                field_type: "email".to_string(),
+                required: true,
+            }],
+            created_at: 10,
+        };
+        Purchase {
+            id: "purchase-1".to_string(),
+            product_id: product.id.clone(),
+            buyer: owner(1),
+            buyer_chain_id: "buyer-chain".to_string(),
+            seller: owner(2),
+            seller_chain_id: "seller-chain".to_string(),
+            amount: product.price,
+            timestamp: 20,
+            order_data: BTreeMap::from([("email".to_string(), "fan@example.com".to_string())]),
+            product,
+            via_application: None,
+        }
+    }
+
+    // The mapping each purchase resolver inlined before they shared purchase_to_full_view
+    fn inlined_full_view(pur: Purchase) -> PurchaseFullView {
+        PurchaseFullView {
+            id: pur.id,
+            product_id: pur.product_id,
+            buyer: pur.buyer,
+            buyer_chain_id: pur.buyer_chain_id,
+            seller: pur.seller,
+            seller_chain_id: pur.seller_chain_id,
+            amount: pur.amount,
+            timestamp: pur.timestamp,
+            order_data: btree_to_pairs(&pur.order_data),
+            product: product_to_full_view(&pur.product),
+        }
+    }
+
+    struct SnapshotQuery(Purchase);
+
+    #[Object]
+    impl SnapshotQuery {
+        async fn before(&self) -> PurchaseFullView {
+            inlined_full_view(self.0.clone())
+        }
+
+        async fn after(&self) -> PurchaseFullView {
+            purchase_to_full_view(&self.0)
+        }
+    }
+
+    const PURCHASE_FIELDS: &str = "{ id productId buyer buyerChainId seller sellerChainId amount timestamp \
+        orderData { key value } \
+        product { id author authorChainId publicData { key value } price privateData { key value } successMessage \
+        orderForm { key label fieldType required } createdAt } }";
+
+    fn snapshot(field: &str) -> serde_json::Value {
+        let schema = Schema::new(SnapshotQuery(sample_purchase()), EmptyMutation, EmptySubscription);
+        let response = schema.execute(format!("{{ {} {} }}", field, PURCHASE_FIELDS)).blocking_wait();
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        let mut data = response.data.into_json().expect("valid JSON");
+        data[field].take()
+    }
+
+    #[test]
+    fn purchase_view_graphql_output_is_unchanged() {
+        let expected = json!({
+            "id": "purchase-1",
+            "productId": "p1",
+            "buyer": owner(1).to_string(),
+            "buyerChainId": "buyer-chain",
+            "seller": owner(2).to_string(),
+            "sellerChainId": "seller-chain",
+            "amount": Amount::from_millis(1_500).to_string(),
+            "timestamp": 20,
+            "orderData": [{ "key": "email", "value": "fan@example.com" }],
+            "product": {
+                "id": "p1",
+                "author": owner(2).to_string(),
+                "authorChainId": "seller-chain",
+                "publicData": [{ "key": "name", "value": "Sketch pack" }],
+                "price": Amount::from_millis(1_500).to_string(),
+                "privateData": [{ "key": "link", "value": "https://example.com/pack" }],
+                "successMessage": "Thanks!",
+                "orderForm": [{ "key": "email", "label": "Email", "fieldType": "email", "required": true }],
+                "createdAt": 10,
+            },
+        });
+        assert_eq!(snapshot("before"), expected);
+        assert_eq!(snapshot("after"), expected);
+    }
+
+    fn chain(n: u8) -> ChainId {
+        format!("{:02x}", n).repeat(32).parse().expect("valid chain id")
+    }
+
+    // A service on `chain_id` whose state holds one sale of a product authored on `author_chain`
+    fn service_with_sale(chain_id: ChainId, author_chain: ChainId) -> DonationsService {
+        let runtime = ServiceRuntime::<DonationsService>::new().with_chain_id(chain_id);
+        let mut state = DonationsState::load(runtime.root_view_storage_context())
+            .blocking_wait()
+            .expect("Failed to read from mock key value store");
+        let mut purchase = sample_purchase();
+        purchase.product.author_chain_id = author_chain.to_string();
+        purchase.seller_chain_id = author_chain.to_string();
+        state.state_version.set(CURRENT_STATE_VERSION);
+        state.create_product(purchase.product.clone()).blocking_wait().unwrap();
+        state.record_purchase(purchase).blocking_wait().unwrap();
+        state.save().blocking_wait().expect("Failed to save state");
+        DonationsService::new(runtime).blocking_wait()
+    }
+
+    fn product_purchases(service: &DonationsService) -> serde_json::Value {
+        let query = "{ productPurchases(productId: \"p1\", limit: 10, offset: 0) { totalCount buyersVisible buyers { buyer orderData { key value } } } }";
+        let response = service.handle_query(Request::new(query)).blocking_wait();
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        let mut data = response.data.into_json().expect("valid JSON");
+        data["productPurchases"].take()
+    }
+
+    #[test]
+    fn product_purchases_returns_buyers_on_the_author_chain() {
+        let service = service_with_sale(chain(2), chain(2));
+        let expected = json!({
+            "totalCount": 1,
+            "buyersVisible": true,
+            "buyers": [{ "buyer": owner(1).to_string(), "orderData": [{ "key": "email", "value": "fan@example.com" }] }],
+        });
+        assert_eq!(product_purchases(&service), expected);
+    }
+
+    #[test]
+    fn product_purchases_withholds_buyers_on_other_chains() {
+        // The main chain mirrors the sale but must only serve the totals
+        let service = service_with_sale(chain(3), chain(2));
+        let expected = json!({ "totalCount": 1, "buyersVisible": false, "buyers": [] });
+        assert_eq!(product_purchases(&service), expected);
+    }
+}
@@ -2,6 +2,7 @@ use linera_sdk::views::{linera_views, MapView, RegisterView, RootView, ViewStora
 use linera_sdk::linera_base_types::{AccountOwner, Amount};
 use donations::{
     Profile, DonationRecord, SocialLink, Product, Purchase, CustomFields, OrderFormField, ContentSubscription, Post, SubscriptionInfo,
+    RevenueBucket, week_bucket, MAX_REVENUE_WEEKS, sanitize_message, CURRENT_STATE_VERSION, OutstandingMessage, ChainActivity, DonationsEvent, DonationDetails,
 };
 
 #[derive(RootView)]
@@ -29,14 +30,36 @@ pub struct DonationsState {
     pub posts: MapView<String, Post>,
     pub posts_by_author: MapView<AccountOwner, Vec<String>>,
     pub posts_by_chain: MapView<String, Vec<String>>,  // NEW: Chain-based index
+    // Outbound messages awaiting acknowledgement
+    pub outstanding_messages: MapView<String, OutstandingMessage>,
+    // Explorer counters keyed by chain_id
+    pub chain_activity: MapView<String, ChainActivity>,
+    // Seller revenue keyed by (seller, week bucket)
+    pub revenue_by_week: MapView<(AccountOwner, u64), Amount>,
+    // Purchase ids per product, for the author's buyer list
+    pub purchases_by_product: MapView<String, Vec<String>>,
+    // Burned donation shares: burns made on this chain, plus (on the main chain) burns reported by user chains
+    pub total_burned: RegisterView<Amount>,
+    // Details of donations that carry any, keyed by donation id
+    pub donation_details: MapView<u64, DonationDetails>,
+    // Calling application of purchases made through Operation::ApplicationCall, keyed by purchase id
+    pub purchase_applications: MapView<String, String>,
+    // Schema version, and the last purchase id the running migration has processed
+    pub state_version: RegisterView<u32>,
+    pub migration_cursor: RegisterView<Option<String>>,
+    // Buyer chain that ProductPurchased sent the private data to, keyed by purchase id (main chain only)
+    pub product_data_sent: MapView<String, String>,
 }
 
 #[allow(dead_code)]
 impl DonationsState {
-    pub async fn record_donation(&mut self, from: AccountOwner, to: AccountOwner, amount: Amount, message: Option<String>, source_chain_id: Option<String>, to_chain_id: Option<String>, timestamp: u64) -> Result<u64, String> {
+    pub async fn record_donation(&mut self, from: AccountOwner, to: AccountOwner, amount: Amount, message: Option<String>, source_chain_id: Option<String>, to_chain_id: Option<String>, details: DonationDetails, timestamp: u64) -> Result<u64, String> {
         let id = *self.donation_counter.get() + 1;
         self.donation_counter.set(id);
-        let rec = DonationRecord { id, timestamp, from: from.clone(), to: to.clone(), amount, message, source_chain_id, to_chain_id };
+        if !details.is_empty() {
+            self.donation_details.insert(&id, details).map_err(|e: ViewError| format!("{:?}", e))?;
+        }
+        let rec = DonationRecord { id, timestamp, from: from.clone(), to: to.clone(), amount, burned_amount: Amount::ZERO, message, message_sanitized: None, source_chain_id, to_chain_id, via_application: None };
         self.donations.insert(&id, rec).map_err(|e: ViewError| format!("{:?}", e))?;
         let mut r = self.donations_by_recipient.get(&to).await.map_err(|e: ViewError| format!("{:?}", e))?.unwrap_or_default();
         r.push(id);
@@ -118,17 +141,36 @@ impl DonationsState {
         self.profiles.get(&owner).await.map_err(|e: ViewError| format!("{:?}", e))
     }
 
+    // Donation record with its sanitized message and details filled in
+    pub async fn get_donation(&self, id: u64) -> Result<Option<DonationRecord>, String> {
+        let mut rec = self.donations.get(&id).await.map_err(|e: ViewError| format!("{:?}", e))?;
+        if let Some(r) = rec.as_mut() {
+            r.message_sanitized = r.message.as_deref().map(sanitize_message);
+            if let Some(details) = self.donation_details.get(&id).await.map_err(|e: ViewError| format!("{:?}", e))? {
+                r.burned_amount = details.burned_amount;
+                r.via_application = details.via_application;
+            }
+        }
+        Ok(rec)
+    }
+
+    pub fn add_burned(&mut self, amount: Amount) {
+        if amount == Amount::ZERO { return; }
+        let total = self.total_burned.get().saturating_add(amount);
+        self.total_burned.set(total);
+    }
+
     pub async fn list_donations_by_recipient(&self, owner: AccountOwner) -> Result<Vec<DonationRecord>, String> {
         let ids = self.donations_by_recipient.get(&owner).await.map_err(|e: ViewError| format!("{:?}", e))?.unwrap_or_default();
         let mut res = Vec::with_capacity(ids.len());
-        for id in ids { if let Some(r) = self.donations.get(&id).await.map_err(|e: ViewError| format!("{:?}", e))? { res.push(r); } }
+        for id in ids { if let Some(r) = self.get_donation(id).await? { res.push(r); } }
         Ok(res)
     }
 
     pub async fn list_donations_by_donor(&self, owner: AccountOwner) -> Result<Vec<DonationRecord>, String> {
         let ids = self.donations_by_donor.get(&owner).await.map_err(|e: ViewError| format!("{:?}", e))?.unwrap_or_default();
         let mut res = Vec::with_capacity(ids.len());
-        for id in ids { if let Some(r) = self.donations.get(&id).await.map_err(|e: ViewError| format!("{:?}", e))? { res.push(r); } }
+        for id in ids { if let Some(r) = self.get_donation(id).await? { res.push(r); } }
         Ok(res)
     }
 
@@ -239,8 +281,30 @@ impl DonationsState {
         let purchase_id = purchase.id.clone();
         let buyer = purchase.buyer.clone();
         let seller = purchase.seller.clone();
+        let product_id = purchase.product_id.clone();
+        if let Some(app) = purchase.via_application.clone() {
+            self.set_purchase_application(&purchase_id, app)?;
+        }
+        
+        // Only index and count revenue the first time a purchase is seen (messages and events can both deliver it)
+        let is_new = !self.purchases.contains_key(&purchase_id).await.map_err(|e: ViewError| format!("{:?}", e))?;
+        if is_new {
+            // A chain's first purchase has nothing to backfill, so the chain starts at the current version
+            if !self.is_migrated() && self.purchases.count().await.map_err(|e: ViewError| format!("{:?}", e))? == 0 {
+                self.state_version.set(CURRENT_STATE_VERSION);
+            }
+            self.add_revenue(seller, purchase.timestamp, purchase.amount).await?;
+            
+            // Index by product
+            let mut product_purchases = self.purchases_by_product.get(&product_id).await.map_err(|e: ViewError| format!("{:?}", e))?.unwrap_or_default();
+            product_purchases.push(purchase_id.clone());
+            self.purchases_by_product.insert(&product_id, product_purchases).map_err(|e: ViewError| format!("{:?}", e))?;
+        }
         
         self.purchases.insert(&purchase_id, purchase).map_err(|e: ViewError| format!("{:?}", e))?;
+        if !is_new {
+            return Ok(());
+        }
         
         // Index by buyer
         let mut buyer_purchases = self.purchases_by_buyer.get(&buyer).await.map_err(|e: ViewError| format!("{:?}", e))?.unwrap_or_default();
@@ -255,11 +319,74 @@ impl DonationsState {
         Ok(())
     }
 
+    // Seller revenue time series
+    pub async fn add_revenue(&mut self, seller: AccountOwner, timestamp: u64, amount: Amount) -> Result<(), String> {
+        let key = (seller, week_bucket(timestamp));
+        let current = self.revenue_by_week.get(&key).await.map_err(|e: ViewError| format!("{:?}", e))?.unwrap_or(Amount::ZERO);
+        self.revenue_by_week.insert(&key, current.saturating_add(amount)).map_err(|e: ViewError| format!("{:?}", e))
+    }
+    
+    // Refunds decrement the week of the original sale, not the week the refund lands in.
+    // There is no refund flow yet; it must go through this so the series stays consistent.
+    pub async fn subtract_revenue(&mut self, seller: AccountOwner, sale_timestamp: u64, amount: Amount) -> Result<(), String> {
+        let key = (seller, week_bucket(sale_timestamp));
+        let current = self.revenue_by_week.get(&key).await.map_err(|e: ViewError| format!("{:?}", e))?.unwrap_or(Amount::ZERO);
+        self.revenue_by_week.insert(&key, current.saturating_sub(amount)).map_err(|e: ViewError| format!("{:?}", e))
+    }
+    
+    /// Zero-filled buckets for `from_week..=to_week`, clamped to MAX_REVENUE_WEEKS buckets
+    pub async fn revenue_series(&self, seller: AccountOwner, from_week: u64, to_week: u64) -> Result<Vec<RevenueBucket>, String> {
+        if to_week < from_week {
+            return Ok(Vec::new());
+        }
+        let to_week = to_week.min(from_week.saturating_add(MAX_REVENUE_WEEKS - 1));
+        let mut res = Vec::new();
+        for week in from_week..=to_week {
+            let amount = self.revenue_by_week.get(&(seller, week)).await.map_err(|e: ViewError| format!("{:?}", e))?.unwrap_or(Amount::ZERO);
+            res.push(RevenueBucket { week, amount });
+        }
+        Ok(res)
+    }
+
+    pub fn set_purchase_application(&mut self, purchase_id: &str, app: String) -> Result<(), String> {
+        self.purchase_applications.insert(&purchase_id.to_string(), app).map_err(|e: ViewError| format!("{:?}", e))
+    }
+
+    // Only sales validated by the ProductPurchased handler are marked; stream mirrors never are
+    pub fn mark_product_data_sent(&mut self, purchase_id: &str, buyer_chain_id: String) -> Result<(), String> {
+        self.product_data_sent.insert(&purchase_id.to_string(), buyer_chain_id).map_err(|e: ViewError| format!("{:?}", e))
+    }
+
+    pub async fn product_data_sent_to(&self, purchase_id: &str) -> Result<Option<String>, String> {
+        self.product_data_sent.get(&purchase_id.to_string()).await.map_err(|e: ViewError| format!("{:?}", e))
+    }
+
+    // Purchase with its calling application filled in
+    pub async fn get_purchase(&self, purchase_id: &str) -> Result<Option<Purchase>, String> {
+        let id = purchase_id.to_string();
+        let mut purchase = self.purchases.get(&id).await.map_err(|e: ViewError| format!("{:?}", e))?;
+        if let Some(p) = purchase.as_mut() {
+            p.via_application = self.purchase_applications.get(&id).await.map_err(|e: ViewError| format!("{:?}", e))?;
+        }
+        Ok(purchase)
+    }
+
     pub async fn list_purchases_by_buyer(&self, buyer: AccountOwner) -> Result<Vec<Purchase>, String> {
         let ids = self.purchases_by_buyer.get(&buyer).await.map_err(|e: ViewError| format!("{:?}", e))?.unwrap_or_default();
         let mut res = Vec::with_capacity(ids.len());
         for id in ids {
-            if let Some(p) = self.purchases.get(&id).await.map_err(|e: ViewError| format!("{:?}", e))? {
+            if let Some(p) = self.get_purchase(&id).await? {
+                res.push(p);
+            }
+        }
+        Ok(res)
+    }
+
+    pub async fn list_purchases_by_product(&self, product_id: &str) -> Result<Vec<Purchase>, String> {
+        let ids = self.purchases_by_product.get(&product_id.to_string()).await.map_err(|e: ViewError| format!("{:?}", e))?.unwrap_or_default();
+        let mut res = Vec::with_capacity(ids.len());
+        for id in ids {
+            if let Some(p) = self.get_purchase(&id).await? {
                 res.push(p);
             }
         }
@@ -270,7 +397,7 @@ impl DonationsState {
         let ids = self.purchases_by_seller.get(&seller).await.map_err(|e: ViewError| format!("{:?}", e))?.unwrap_or_default();
         let mut res = Vec::with_capacity(ids.len());
         for id in ids {
-            if let Some(p) = self.purchases.get(&id).await.map_err(|e: ViewError| format!("{:?}", e))? {
+            if let Some(p) = self.get_purchase(&id).await? {
                 res.push(p);
             }
         }
@@ -412,4 +539,123 @@ impl DonationsState {
         
         Ok(())
     }
-}
\ No newline at end of file
+    
+    // Explorer counters - increment only, updated as events and messages arrive
+    pub async fn record_chain_activity(&mut self, chain_id: String, is_donation: bool, timestamp: u64) -> Result<(), String> {
+        let mut activity = self.chain_activity.get(&chain_id).await.map_err(|e: ViewError| format!("{:?}", e))?.unwrap_or_else(|| ChainActivity { chain_id: chain_id.clone(), ..Default::default() });
+        activity.event_count += 1;
+        if is_donation { activity.donation_count += 1; }
+        activity.last_event_at = Some(timestamp);
+        self.chain_activity.insert(&chain_id, activity).map_err(|e: ViewError| format!("{:?}", e))
+    }
+    
+    /// Count an event mirrored from `stream_chain_id`'s donations_events stream
+    pub async fn record_stream_event(&mut self, stream_chain_id: String, event: &DonationsEvent, timestamp: u64) -> Result<(), String> {
+        let is_donation = event.is_donation_received_on(&stream_chain_id);
+        self.record_chain_activity(stream_chain_id, is_donation, timestamp).await
+    }
+    
+    /// Record a donation mirrored from `stream_chain_id`'s stream. A cross-chain donation is
+    /// emitted by both the sender's and the recipient's chain; only the recipient's copy is kept.
+    pub async fn mirror_donation(&mut self, stream_chain_id: &str, event: DonationsEvent) -> Result<(), String> {
+        if !event.is_donation_received_on(stream_chain_id) {
+            return Ok(());
+        }
+        match event {
+            DonationsEvent::DonationSent { id: _, from, to, amount, message, source_chain_id, to_chain_id, timestamp } => {
+                self.record_donation(from, to, amount, message, source_chain_id, to_chain_id, DonationDetails::default(), timestamp).await?;
+            }
+            DonationsEvent::DonationSentWithDetails { id: _, from, to, amount, message, source_chain_id, to_chain_id, timestamp, details } => {
+                self.record_donation(from, to, amount, message, source_chain_id, to_chain_id, details, timestamp).await?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+    
+    pub async fn get_chain_activity(&self, chain_id: &str) -> Result<Option<ChainActivity>, String> {
+        self.chain_activity.get(&chain_id.to_string()).await.map_err(|e: ViewError| format!("{:?}", e))
+    }
+    
+    // State versioning
+    pub fn is_migrated(&self) -> bool {
+        *self.state_version.get() >= CURRENT_STATE_VERSION
+    }
+    
+    /// Whether queries over migrated structures must wait (usable without a contract load)
+    pub async fn migration_required(&self) -> Result<bool, String> {
+        if self.is_migrated() {
+            return Ok(false);
+        }
+        Ok(self.purchases.count().await.map_err(|e: ViewError| format!("{:?}", e))? > 0)
+    }
+    
+    /// v0 -> v1: backfill revenue_by_week and purchases_by_product from existing purchases.
+    /// Processes at most `batch_size` purchases after `migration_cursor` (purchases are never removed,
+    /// so the cursor is always found) and returns true once the migration is complete.
+    /// Purchases already present in the product index were counted by record_purchase and are skipped.
+    pub async fn migrate_step(&mut self, batch_size: usize) -> Result<bool, String> {
+        if self.is_migrated() {
+            return Ok(true);
+        }
+        let cursor = self.migration_cursor.get().clone();
+        let mut past_cursor = cursor.is_none();
+        let mut ids = Vec::with_capacity(batch_size);
+        let mut more = false;
+        self.purchases.for_each_index_while(|id| {
+            if !past_cursor {
+                past_cursor = cursor.as_ref() == Some(&id);
+                return Ok(true);
+            }
+            if ids.len() == batch_size {
+                more = true;
+                return Ok(false);
+            }
+            ids.push(id);
+            Ok(true)
+        }).await.map_err(|e: ViewError| format!("{:?}", e))?;
+        for id in &ids {
+            if let Some(p) = self.purchases.get(id).await.map_err(|e: ViewError| format!("{:?}", e))? {
+                let mut product_purchases = self.purchases_by_product.get(&p.product_id).await.map_err(|e: ViewError| format!("{:?}", e))?.unwrap_or_default();
+                if !product_purchases.contains(id) {
+                    self.add_revenue(p.seller, p.timestamp, p.amount).await?;
+                    product_purchases.push(id.clone());
+                    self.purchases_by_product.insert(&p.product_id, product_purchases).map_err(|e: ViewError| format!("{:?}", e))?;
+                }
+            }
+        }
+        if more {
+            self.migration_cursor.set(ids.pop());
+            Ok(false)
+        } else {
+            self.state_version.set(CURRENT_STATE_VERSION);
+            self.migration_cursor.set(None);
+            Ok(true)
+        }
+    }
+    
+    // Outbound message tracking
+    pub async fn track_outstanding(&mut self, entry: OutstandingMessage) -> Result<(), String> {
+        let id = entry.id.clone();
+        self.outstanding_messages.insert(&id, entry).map_err(|e: ViewError| format!("{:?}", e))
+    }
+    
+    pub async fn acknowledge_outstanding(&mut self, id: &str) -> Result<(), String> {
+        self.outstanding_messages.remove(&id.to_string()).map_err(|e: ViewError| format!("{:?}", e))
+    }
+    
+    pub async fn get_outstanding(&self, id: &str) -> Result<Option<OutstandingMessage>, String> {
+        self.outstanding_messages.get(&id.to_string()).await.map_err(|e: ViewError| format!("{:?}", e))
+    }
+    
+    pub async fn list_outstanding(&self) -> Result<Vec<OutstandingMessage>, String> {
+        let ids = self.outstanding_messages.indices().await.map_err(|e: ViewError| format!("{:?}", e))?;
+        let mut res = Vec::with_capacity(ids.len());
+        for id in ids {
+            if let Some(m) = self.outstanding_messages.get(&id).await.map_err(|e: ViewError| format!("{:?}", e))? {
+                res.push(m);
+            }
+        }
+        Ok(res)
+    }
+}